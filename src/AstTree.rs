@@ -1,36 +1,30 @@
 use std::collections::HashMap;
-use std::str;
-use std::time::Instant;
+use std::fmt;
 
-use crate::tokenizer::{Types, DataHolder, Tokens, Callable, ComparisonOperator, LogicalOperator, ConditionalExpression, ExpressionNode, ArithmeticOperator};
-use crate::Environment::Environment;
+use crate::tokenizer::{Types, Token, Tokens, Span, ComparisonOperator, LogicalOperator, ArithmeticOperator};
+use crate::values::DataHolder;
 
 #[derive(Debug, Clone)]
 pub enum Statement {
     VariableDeclaration {
         name: String,
-        data_type: Types,
+        /// `None` when the annotation is left out; the type is then inferred from the value.
+        data_type: Option<Types>,
         value: AstExpressions,
-    },
-    ListDeclaration {
-        name: String,
-        elements: Vec<AstExpressions>,
-        size: usize,
-    },
-    Function {
-        function: Callable,
-        parameters: Vec<AstExpressions>,
-        environment: Environment,
+        span: Span,
     },
     FunctionDeclaration {
         name: String,
         params: Vec<FunctionParameter>,
+        return_type: Option<Types>,
         body: Vec<Statement>,
+        span: Span,
     },
     Conditional {
         condition: AstExpressions,
         then_branch: Vec<Statement>,
         else_branch: Option<Vec<Statement>>,
+        span: Span,
     },
     ForLoop {
         variable: String,
@@ -38,51 +32,131 @@ pub enum Statement {
         end: AstExpressions,
         step: AstExpressions,
         body: Vec<Statement>,
+        span: Span,
+    },
+    /// `for variable in iterable { ... }`
+    ForEach {
+        variable: String,
+        iterable: AstExpressions,
+        body: Vec<Statement>,
+        span: Span,
     },
     WhileLoop {
         condition: AstExpressions,
         body: Vec<Statement>,
+        span: Span,
+    },
+    Block {
+        statements: Vec<Statement>,
+        span: Span,
     },
-    Block(Vec<Statement>),
     Assignment {
         name: String,
         value: AstExpressions,
+        span: Span,
     },
     
     MemberAssignment {
         object: AstExpressions,
         member: String,
         value: AstExpressions,
+        span: Span,
+    },
+
+    IndexAssignment {
+        object: AstExpressions,
+        index: AstExpressions,
+        value: AstExpressions,
+        span: Span,
+    },
+
+    /// `target op= value`, where `target` is a variable, member access or index.
+    CompoundAssignment {
+        target: AstExpressions,
+        operator: ArithmeticOperator,
+        value: AstExpressions,
+        span: Span,
     },
     ExpressionStatement {
         expression: AstExpressions,
+        span: Span,
     },
     
     Return {
         value: Option<AstExpressions>,
+        span: Span,
+    },
+
+    Break {
+        span: Span,
+    },
+
+    Continue {
+        span: Span,
     },
 
     ClassMeta {
         name: String,
         fields: HashMap<String, Statement>,
+        span: Span,
     },
 
     ClassAttribute {
         name: String,
         data_type: Types,
+        span: Span,
     }
 }
 
-#[derive(Debug, Clone)]
-struct StructField {
-    variable_map: HashMap<String, AstExpressions>,
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::VariableDeclaration { span, .. }
+            | Statement::FunctionDeclaration { span, .. }
+            | Statement::Conditional { span, .. }
+            | Statement::ForLoop { span, .. }
+            | Statement::ForEach { span, .. }
+            | Statement::WhileLoop { span, .. }
+            | Statement::Block { span, .. }
+            | Statement::Assignment { span, .. }
+            | Statement::MemberAssignment { span, .. }
+            | Statement::IndexAssignment { span, .. }
+            | Statement::CompoundAssignment { span, .. }
+            | Statement::ExpressionStatement { span, .. }
+            | Statement::Return { span, .. }
+            | Statement::Break { span }
+            | Statement::Continue { span }
+            | Statement::ClassMeta { span, .. }
+            | Statement::ClassAttribute { span, .. } => *span,
+        }
+    }
+}
+
+/// The fields declared in a class's members, in the order they appear in the source.
+pub fn class_attributes(members: &HashMap<String, Statement>) -> Vec<(String, Types)> {
+    let mut attributes: Vec<_> = members.values()
+        .filter_map(|member| match member {
+            Statement::ClassAttribute { name, data_type, span } => Some((span.start, name.clone(), data_type.clone())),
+            _ => None,
+        })
+        .collect();
+    attributes.sort_by_key(|(start, ..)| *start);
+    attributes.into_iter().map(|(_, name, data_type)| (name, data_type)).collect()
 }
 
 
+/// `name: value` in a call.
+pub type KeywordArgument = (String, AstExpressions);
+
 #[derive(Debug, Clone)]
 pub struct FunctionParameter {
     pub name: String,
     pub data_type: Types,
+    /// `name: type = default`. Evaluated at each call that leaves the parameter out, in the
+    /// function's scope, so it can use the parameters before it.
+    pub default: Option<AstExpressions>,
+    /// `...name: list`, which collects the positional arguments left over. Always last.
+    pub rest: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -91,419 +165,238 @@ pub enum AstExpressions {
         left: Box<AstExpressions>,
         operator: ArithmeticOperator,
         right: Box<AstExpressions>,
+        span: Span,
     },
     UnaryOperation {
         operator: ArithmeticOperator,
         operand: Box<AstExpressions>,
+        span: Span,
     },
     ComparisonOperation {
         left: Box<AstExpressions>,
         operator: ComparisonOperator,
         right: Box<AstExpressions>,
+        span: Span,
     },
+    /// `and`/`&&` and `or`/`||`. Both operands must be bools and the result is a bool, not
+    /// one of the operands as in Python. The right operand is only evaluated when the left one
+    /// doesn't already decide the result.
     LogicalOperation {
         left: Box<AstExpressions>,
         operator: LogicalOperator,
         right: Box<AstExpressions>,
+        span: Span,
     },
     Value {
-        value: DataHolder
+        value: DataHolder,
+        span: Span,
     },
     Variable {
-        name: String
-    },
-    Literal {
-        value: String
+        name: String,
+        span: Span,
     },
     ListLiteral {
-        elements: Vec<AstExpressions>
+        elements: Vec<AstExpressions>,
+        span: Span,
     },
+    /// `{ key: value, ... }`
+    MapLiteral {
+        entries: Vec<(AstExpressions, AstExpressions)>,
+        span: Span,
+    },
+    /// A call to a name: a class (constructing an instance), a variable holding a function, a
+    /// declared function or a builtin.
     FunctionCall {
         name: String,
         arguments: Vec<AstExpressions>,
+        /// `name: value` arguments, which follow the positional ones.
+        keyword_arguments: Vec<KeywordArgument>,
+        span: Span,
+    },
+    /// A call to whatever `callee` evaluates to, e.g. `handlers[0](x)` or `make_adder(1)(2)`.
+    Call {
+        callee: Box<AstExpressions>,
+        arguments: Vec<AstExpressions>,
+        keyword_arguments: Vec<KeywordArgument>,
+        span: Span,
+    },
+    /// `Person { name: "Ann", age: 30 }`: an instance with every field given a value.
+    /// `__init__` is not run.
+    InstanceLiteral {
+        class_name: String,
+        fields: Vec<(String, AstExpressions)>,
+        span: Span,
+    },
+    /// `fn (params) -> type { body }`, an anonymous function.
+    Lambda {
+        params: Vec<FunctionParameter>,
+        return_type: Option<Types>,
+        body: Vec<Statement>,
+        span: Span,
     },
     
     MemberAccess {
         object: Box<AstExpressions>,
         member: String,
+        span: Span,
     },
     
     MethodCall {
         object: Box<AstExpressions>,
         method: String,
         arguments: Vec<AstExpressions>,
+        keyword_arguments: Vec<KeywordArgument>,
+        span: Span,
     },
     Grouping {
-        expression: Box<AstExpressions>
+        expression: Box<AstExpressions>,
+        span: Span,
+    },
+    Index {
+        object: Box<AstExpressions>,
+        index: Box<AstExpressions>,
+        span: Span,
+    },
+    /// `object[start:end]`; either bound may be left out.
+    Slice {
+        object: Box<AstExpressions>,
+        start: Option<Box<AstExpressions>>,
+        end: Option<Box<AstExpressions>>,
+        span: Span,
     },
 }
 
-
 impl AstExpressions {
-    pub fn evaluate(&self, env: &Environment) -> Option<DataHolder> {
+    pub fn span(&self) -> Span {
         match self {
-            AstExpressions::Value { value } => Some(value.clone()),
-            
-            AstExpressions::Variable { name } => {
-                env.get_variable(name).cloned()
-            },
-            
-            AstExpressions::Literal { value } => {
-                Some(DataHolder::STRING(value.clone()))
-            },
-            
-            AstExpressions::BinaryOperation { left, operator, right } => {
-                let left_val = left.evaluate(env)?;
-                let right_val = right.evaluate(env)?;
-                self.perform_arithmetic_operation(&left_val, operator, &right_val)
-            },
-            
-            AstExpressions::UnaryOperation { operator, operand } => {
-                let operand_val = operand.evaluate(env)?;
-                self.perform_unary_operation(operator, &operand_val)
-            },
-            
-            AstExpressions::ComparisonOperation { left, operator, right } => {
-                let left_val = left.evaluate(env)?;
-                let right_val = right.evaluate(env)?;
-                self.perform_comparison_operation(&left_val, operator, &right_val)
-            },
-            
-            AstExpressions::LogicalOperation { left, operator, right } => {
-                let left_val = left.evaluate(env)?;
-                
-                match operator {
-                    LogicalOperator::And => {
-                        if let DataHolder::BOOLEAN(false) = left_val {
-                            Some(DataHolder::BOOLEAN(false)) 
-                        } else {
-                            let right_val = right.evaluate(env)?;
-                            match (left_val, right_val) {
-                                (DataHolder::BOOLEAN(a), DataHolder::BOOLEAN(b)) => 
-                                    Some(DataHolder::BOOLEAN(a && b)),
-                                _ => None,
-                            }
-                        }
-                    },
-                    LogicalOperator::Or => {
-                        if let DataHolder::BOOLEAN(true) = left_val {
-                            Some(DataHolder::BOOLEAN(true)) 
-                        } else {
-                            let right_val = right.evaluate(env)?;
-                            match (left_val, right_val) {
-                                (DataHolder::BOOLEAN(a), DataHolder::BOOLEAN(b)) => 
-                                    Some(DataHolder::BOOLEAN(a || b)),
-                                _ => None,
-                            }
-                        }
-                    },
-                }
-            },
-            
-            AstExpressions::ListLiteral { elements } => {
-                let mut evaluated_elements = Vec::new();
-                for element in elements {
-                    if let Some(val) = element.evaluate(env) {
-                        evaluated_elements.push(val);
-                    } else {
-                        return None;
-                    }
-                }
-                Some(DataHolder::LIST(evaluated_elements))
-            },
-            
-            AstExpressions::FunctionCall { name, arguments } => {
-                let mut evaluated_args = Vec::new();
-                for arg in arguments {
-                    if let Some(val) = arg.evaluate(env) {
-                        evaluated_args.push(val);
-                    } else {
-                        return None;
-                    }
-                }
-                self.execute_function_call(name, evaluated_args, env)
-            },
-            
-            AstExpressions::Grouping { expression } => {
-                expression.evaluate(env)
-            },
-            AstExpressions::MemberAccess { object, member } => {
-                let obj_val = object.evaluate(env)?;
-                match obj_val {
-                    DataHolder::CLASSINSTANCE(ref instance) => {
-                        if let Some(field_val) = instance.fields.get(member) {
-                            Some(field_val.clone())
-                        } else {
-                            None
-                        }
-                    },
-                    _ => None,
-                }
-            },
-            AstExpressions::MethodCall { object, method, arguments } => {
-                let obj_val = object.evaluate(env)?;
-                let mut evaluated_args = Vec::new();
-                for arg in arguments {
-                    if let Some(val) = arg.evaluate(env) {
-                        evaluated_args.push(val);
-                    } else {
-                        return None;
-                    }
-                }
-                match obj_val {
-                    DataHolder::CLASSINSTANCE(ref instance) => {
-                        
-                        
-                        
-                        eprintln!("Method calls should be handled in runtime, not during AST evaluation");
-                        None
-                    },
-                    _ => None,
-                }
-            },
-        }
-    }
-    
-    fn perform_arithmetic_operation(&self, left: &DataHolder, operator: &ArithmeticOperator, right: &DataHolder) -> Option<DataHolder> {
-        match operator {
-            ArithmeticOperator::Add => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::INTEGER32(a + b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::INTEGER64(a + b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::FLOAT32(a + b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::FLOAT64(a + b)),
-                    (DataHolder::STRING(a), DataHolder::STRING(b)) => Some(DataHolder::STRING(format!("{}{}", a, b))),
-                    _ => None,
-                }
-            },
-            ArithmeticOperator::Subtract => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::INTEGER32(a - b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::INTEGER64(a - b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::FLOAT32(a - b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::FLOAT64(a - b)),
-                    _ => None,
-                }
-            },
-            ArithmeticOperator::Multiply => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::INTEGER32(a * b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::INTEGER64(a * b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::FLOAT32(a * b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::FLOAT64(a * b)),
-                    _ => None,
-                }
-            },
-            ArithmeticOperator::Divide => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => {
-                        if *b == 0 { None } else { Some(DataHolder::INTEGER32(a / b)) }
-                    },
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => {
-                        if *b == 0 { None } else { Some(DataHolder::INTEGER64(a / b)) }
-                    },
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => {
-                        if *b == 0.0 { None } else { Some(DataHolder::FLOAT32(a / b)) }
-                    },
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => {
-                        if *b == 0.0 { None } else { Some(DataHolder::FLOAT64(a / b)) }
-                    },
-                    _ => None,
-                }
-            },
-            ArithmeticOperator::Modulo => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => {
-                        if *b == 0 { None } else { Some(DataHolder::INTEGER32(a % b)) }
-                    },
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => {
-                        if *b == 0 { None } else { Some(DataHolder::INTEGER64(a % b)) }
-                    },
-                    _ => None,
-                }
-            },
-            ArithmeticOperator::Not => {
-                match left {
-                    DataHolder::BOOLEAN(b) => Some(DataHolder::BOOLEAN(!b)),
-                    DataHolder::INTEGER32(i) => Some(DataHolder::BOOLEAN(*i == 0)),
-                    DataHolder::INTEGER64(i) => Some(DataHolder::BOOLEAN(*i == 0)),
-                    _ => None,
-                }
-            }
-        }
-    }
-    
-    fn perform_unary_operation(&self, operator: &ArithmeticOperator, operand: &DataHolder) -> Option<DataHolder> {
-        match operator {
-            ArithmeticOperator::Subtract => {
-                match operand {
-                    DataHolder::INTEGER32(n) => Some(DataHolder::INTEGER32(-n)),
-                    DataHolder::INTEGER64(n) => Some(DataHolder::INTEGER64(-n)),
-                    DataHolder::FLOAT32(n) => Some(DataHolder::FLOAT32(-n)),
-                    DataHolder::FLOAT64(n) => Some(DataHolder::FLOAT64(-n)),
-                    _ => None,
-                }
-            },
-            ArithmeticOperator::Add => Some(operand.clone()), 
-            _ => None, 
-        }
-    }
-    
-    fn perform_comparison_operation(&self, left: &DataHolder, operator: &ComparisonOperator, right: &DataHolder) -> Option<DataHolder> {
-        match operator {
-            ComparisonOperator::Equal => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::BOOLEAN(a == b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::BOOLEAN(a == b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::BOOLEAN((a - b).abs() < f32::EPSILON)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::BOOLEAN((a - b).abs() < f64::EPSILON)),
-                    (DataHolder::STRING(a), DataHolder::STRING(b)) => Some(DataHolder::BOOLEAN(a == b)),
-                    (DataHolder::BOOLEAN(a), DataHolder::BOOLEAN(b)) => Some(DataHolder::BOOLEAN(a == b)),
-                    _ => Some(DataHolder::BOOLEAN(false)),
-                }
-            },
-            ComparisonOperator::NotEqual => {
-                if let Some(DataHolder::BOOLEAN(result)) = self.perform_comparison_operation(left, &ComparisonOperator::Equal, right) {
-                    Some(DataHolder::BOOLEAN(!result))
-                } else {
-                    None
-                }
-            },
-            ComparisonOperator::Greater => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::BOOLEAN(a > b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::BOOLEAN(a > b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::BOOLEAN(a > b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::BOOLEAN(a > b)),
-                    _ => None,
-                }
-            },
-            ComparisonOperator::Less => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::BOOLEAN(a < b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::BOOLEAN(a < b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::BOOLEAN(a < b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::BOOLEAN(a < b)),
-                    _ => None,
-                }
-            },
-            ComparisonOperator::GreaterEqual => {
-                if let Some(DataHolder::BOOLEAN(less_result)) = self.perform_comparison_operation(left, &ComparisonOperator::Less, right) {
-                    Some(DataHolder::BOOLEAN(!less_result))
-                } else {
-                    None
-                }
-            },
-            ComparisonOperator::LessEqual => {
-                if let Some(DataHolder::BOOLEAN(greater_result)) = self.perform_comparison_operation(left, &ComparisonOperator::Greater, right) {
-                    Some(DataHolder::BOOLEAN(!greater_result))
-                } else {
-                    None
-                }
-            },
-        }
-    }
-    
-    fn execute_function_call(&self, func_name: &str, args: Vec<DataHolder>, env: &Environment) -> Option<DataHolder> {
-        match func_name {
-            "print" => {
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 0 { print!(" "); }
-                    match arg {
-                        DataHolder::INTEGER32(n) => print!("{}", n),
-                        DataHolder::INTEGER64(n) => print!("{}", n),
-                        DataHolder::FLOAT32(n) => print!("{}", n),
-                        DataHolder::FLOAT64(n) => print!("{}", n),
-                        DataHolder::STRING(s) => print!("{}", s),
-                        DataHolder::BOOLEAN(b) => print!("{}", b),
-                        DataHolder::LIST(list) => print!("{:?}", list),
-                        _ => print!("{:?}", arg),
-                    }
-                }
-                println!();
-                Some(DataHolder::INTEGER32(0))
-            },
-            "println" => {
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 0 { print!(" "); }
-                    match arg {
-                        DataHolder::INTEGER32(n) => print!("{}", n),
-                        DataHolder::INTEGER64(n) => print!("{}", n),
-                        DataHolder::FLOAT32(n) => print!("{}", n),
-                        DataHolder::FLOAT64(n) => print!("{}", n),
-                        DataHolder::STRING(s) => print!("{}", s),
-                        DataHolder::BOOLEAN(b) => print!("{}", b),
-                        DataHolder::LIST(list) => print!("{:?}", list),
-                        _ => print!("{:?}", arg),
-                    }
-                }
-                println!();
-                Some(DataHolder::INTEGER32(0))
-            },
-            "len" => {
-                if args.len() == 1 {
-                    match &args[0] {
-                        DataHolder::STRING(s) => Some(DataHolder::INTEGER32(s.len() as i32)),
-                        DataHolder::LIST(list) => Some(DataHolder::INTEGER32(list.len() as i32)),
-                        _ => None,
-                    }
-                } else {
-                    None
-                }
-            },
-            "millis_as_str" => {
-                Some(DataHolder::STRING(Instant::now().elapsed().as_millis().to_string()))
-            },
-            _ => {
-                None
-            }
+            AstExpressions::BinaryOperation { span, .. }
+            | AstExpressions::UnaryOperation { span, .. }
+            | AstExpressions::ComparisonOperation { span, .. }
+            | AstExpressions::LogicalOperation { span, .. }
+            | AstExpressions::Value { span, .. }
+            | AstExpressions::Variable { span, .. }
+            | AstExpressions::ListLiteral { span, .. }
+            | AstExpressions::MapLiteral { span, .. }
+            | AstExpressions::FunctionCall { span, .. }
+            | AstExpressions::Call { span, .. }
+            | AstExpressions::Lambda { span, .. }
+            | AstExpressions::MemberAccess { span, .. }
+            | AstExpressions::MethodCall { span, .. }
+            | AstExpressions::Grouping { span, .. }
+            | AstExpressions::Index { span, .. }
+            | AstExpressions::Slice { span, .. }
+            | AstExpressions::InstanceLiteral { span, .. } => *span,
         }
     }
 }
 
+
 struct TokenCursor {
-    tokens: Vec<Tokens>,
+    tokens: Vec<Token>,
     position: usize,
 }
 
 impl TokenCursor {
-    fn new(tokens: Vec<Tokens>) -> Self {
+    fn new(tokens: Vec<Token>) -> Self {
         TokenCursor { tokens, position: 0 }
     }
 
     fn current_token(&self) -> Option<&Tokens> {
-        self.tokens.get(self.position)
+        self.tokens.get(self.position).map(|token| &token.kind)
     }
 
+    /// The token `offset` places after the current one.
     fn peek_token(&self, offset: usize) -> Option<&Tokens> {
-        self.tokens.get(self.position + offset)
+        self.tokens.get(self.position + offset).map(|token| &token.kind)
+    }
+
+    /// Span of the current token, or an empty span just past the last token at end of input.
+    fn current_span(&self) -> Span {
+        match self.tokens.get(self.position) {
+            Some(token) => token.span,
+            None => self.tokens.last()
+                .map(|token| Span { start: token.span.end, ..token.span })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Span of the most recently consumed token.
+    fn previous_span(&self) -> Span {
+        self.position.checked_sub(1)
+            .and_then(|index| self.tokens.get(index))
+            .map(|token| token.span)
+            .unwrap_or_default()
     }
 
     fn consume_token(&mut self) -> Option<&Tokens> {
         if self.position < self.tokens.len() {
             let token = &self.tokens[self.position];
             self.position += 1;
-            Some(token)
+            Some(&token.kind)
         } else {
             None
         }
     }
 
     fn match_token(&mut self, expected: &Tokens) -> bool {
-        if let Some(current) = self.current_token() {
-            if std::mem::discriminant(current) == std::mem::discriminant(expected) {
-                self.consume_token();
-                return true;
-            }
+        if let Some(current) = self.current_token()
+            && std::mem::discriminant(current) == std::mem::discriminant(expected) {
+            self.consume_token();
+            return true;
         }
         false
     }
 
-    fn expect_token(&mut self, expected: &Tokens) -> Option<&Tokens> {
+    fn expect_token(&mut self, expected: &Tokens) -> Result<(), ParseError> {
         if self.match_token(expected) {
-            self.tokens.get(self.position - 1)
+            Ok(())
         } else {
-            None
+            Err(self.error_expected(&expected.to_string()))
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, ParseError> {
+        match self.current_token() {
+            Some(Tokens::IDENTIFIER(name)) => {
+                let name = name.clone();
+                self.consume_token();
+                Ok(name)
+            },
+            _ => Err(self.error_expected("an identifier")),
+        }
+    }
+
+    /// A built-in type keyword, `fn` for functions, or a class name standing for instances of
+    /// that class.
+    fn expect_type(&mut self) -> Result<Types, ParseError> {
+        match self.current_token() {
+            Some(Tokens::TYPE(data_type)) => {
+                let data_type = data_type.clone();
+                self.consume_token();
+                Ok(data_type)
+            },
+            Some(Tokens::FN) => {
+                self.consume_token();
+                Ok(Types::FUNCTION)
+            },
+            Some(Tokens::IDENTIFIER(class_name)) => {
+                let data_type = Types::CLASS(class_name.clone());
+                self.consume_token();
+                Ok(data_type)
+            },
+            _ => Err(self.error_expected("a type")),
+        }
+    }
+
+    /// Builds an "expected X, found Y" error pointing at the current token.
+    fn error_expected(&self, expected: &str) -> ParseError {
+        let found = match self.current_token() {
+            Some(token) => token.to_string(),
+            None => "end of input".to_string(),
+        };
+        ParseError {
+            message: format!("expected {}, found {}", expected, found),
+            span: self.current_span(),
         }
     }
 
@@ -512,29 +405,85 @@ impl TokenCursor {
     }
 }
 
-pub struct ASTParser;
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub struct ASTParser {
+    errors: Vec<ParseError>,
+    /// Number of loops enclosing the statement being parsed, within the current function.
+    loop_depth: usize,
+    /// Set while parsing the iterable of `for x in xs { }`, where `xs { }` is the loop body
+    /// rather than an instance literal.
+    in_loop_header: bool,
+}
 
 impl ASTParser {
     pub fn new() -> Self {
-        ASTParser
+        ASTParser { errors: Vec::new(), loop_depth: 0, in_loop_header: false }
     }
 
-    pub fn parse(&mut self, tokens: Vec<Tokens>) -> Vec<Statement> {
+    /// Parses a whole program. Errors are collected rather than returned at the first one:
+    /// after a failed statement the parser skips ahead to the next statement boundary and
+    /// carries on, so a single run reports every error it can find.
+    pub fn parse(&mut self, tokens: Vec<Token>) -> Result<Vec<Statement>, Vec<ParseError>> {
         let mut cursor = TokenCursor::new(tokens);
         let mut statements = Vec::new();
         
         while !cursor.is_at_end() {
-            if let Some(statement) = self.parse_statement(&mut cursor) {
-                statements.push(statement);
-            } else {
-                cursor.consume_token(); 
+            let statement_start = cursor.position;
+            match self.parse_statement(&mut cursor) {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(&mut cursor, statement_start);
+                }
             }
         }
 
-        statements
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Skips the rest of a broken statement. Stops before a keyword that starts a statement,
+    /// before the brace closing the enclosing block, or before an identifier that begins a
+    /// later line. Blocks opened inside the broken statement are skipped as a whole so their
+    /// closing brace does not produce a second error.
+    fn synchronize(&mut self, cursor: &mut TokenCursor, statement_start: usize) {
+        let error_line = cursor.current_span().line;
+        if cursor.position == statement_start {
+            cursor.consume_token();
+        }
+
+        let mut depth = 0;
+        while let Some(token) = cursor.current_token() {
+            match token {
+                Tokens::LBRACE => depth += 1,
+                Tokens::RBRACE if depth > 0 => depth -= 1,
+                Tokens::RBRACE => return,
+                Tokens::LET | Tokens::IF | Tokens::FOR | Tokens::FN | Tokens::RETURN
+                | Tokens::WHILE | Tokens::CLASS | Tokens::BREAK | Tokens::CONTINUE if depth == 0 => return,
+                Tokens::IDENTIFIER(_) | Tokens::SELF if depth == 0 && cursor.current_span().line > error_line => return,
+                _ => {}
+            }
+            cursor.consume_token();
+        }
     }
 
-    fn parse_statement(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    fn parse_statement(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
         match cursor.current_token() {
             Some(Tokens::LET) => self.parse_variable_declaration(cursor),
             Some(Tokens::IF) => self.parse_conditional_statement(cursor),
@@ -545,17 +494,52 @@ impl ASTParser {
             Some(Tokens::LBRACE) => self.parse_block_statement(cursor),
             Some(Tokens::WHILE) => self.parse_while_loop(cursor),
             Some(Tokens::CLASS) => self.parse_class_declaration(cursor),
-            _ => None,
+            Some(Tokens::SELF) => self.parse_assignment_or_expression(cursor),
+            Some(Tokens::BREAK) | Some(Tokens::CONTINUE) => self.parse_loop_control(cursor),
+            _ => Err(cursor.error_expected("a statement")),
+        }
+    }
+
+    fn parse_loop_control(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let span = cursor.current_span();
+        let is_break = matches!(cursor.consume_token(), Some(Tokens::BREAK));
+        let keyword = if is_break { "break" } else { "continue" };
+
+        if self.loop_depth == 0 {
+            return Err(ParseError {
+                message: format!("'{}' outside of a loop", keyword),
+                span,
+            });
+        }
+
+        if is_break {
+            Ok(Statement::Break { span })
+        } else {
+            Ok(Statement::Continue { span })
         }
     }
 
-    fn parse_function_declaration(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    fn parse_loop_body(&mut self, cursor: &mut TokenCursor) -> Result<Vec<Statement>, ParseError> {
+        self.loop_depth += 1;
+        let body = self.parse_block_body(cursor);
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// Parses a function or method body. Loops around the declaration don't count: `break`
+    /// can't jump out of a function.
+    fn parse_function_body(&mut self, cursor: &mut TokenCursor) -> Result<Vec<Statement>, ParseError> {
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
+        let body = self.parse_block_body(cursor);
+        self.loop_depth = enclosing_loops;
+        body
+    }
+
+    fn parse_function_declaration(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let start_span = cursor.current_span();
         cursor.expect_token(&Tokens::FN)?; 
 
-        let name = match cursor.consume_token()? {
-            Tokens::IDENTIFIER(n) => n.clone(),
-            _ => return None,
-        };
+        let name = cursor.expect_identifier()?;
 
         cursor.expect_token(&Tokens::LPAREN)?;
         
@@ -563,52 +547,107 @@ impl ASTParser {
         
         cursor.expect_token(&Tokens::RPAREN)?; 
 
+        let return_type = self.parse_return_type(cursor)?;
+
         cursor.expect_token(&Tokens::LBRACE)?;
 
-        let body = self.parse_block_body(cursor)?;
+        let body = self.parse_function_body(cursor)?;
         
         cursor.expect_token(&Tokens::RBRACE)?; 
 
-        Some(Statement::FunctionDeclaration {
+        Ok(Statement::FunctionDeclaration {
             name,
             params,
+            return_type,
+            body,
+            span: start_span.to(cursor.previous_span()),
+        })
+    }
+
+    /// `fn (params) -> type { body }`: a function declaration without a name, used as a value.
+    fn parse_lambda(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        let start_span = cursor.current_span();
+        cursor.expect_token(&Tokens::FN)?;
+        cursor.expect_token(&Tokens::LPAREN)?;
+        let params = self.parse_function_parameters(cursor)?;
+        cursor.expect_token(&Tokens::RPAREN)?;
+        let return_type = self.parse_return_type(cursor)?;
+        cursor.expect_token(&Tokens::LBRACE)?;
+        let body = self.parse_function_body(cursor)?;
+        cursor.expect_token(&Tokens::RBRACE)?;
+
+        Ok(AstExpressions::Lambda {
+            params,
+            return_type,
             body,
+            span: start_span.to(cursor.previous_span()),
         })
     }
 
-    fn parse_function_parameters(&mut self, cursor: &mut TokenCursor) -> Option<Vec<FunctionParameter>> {
-        let mut params = Vec::new();
+    fn parse_return_type(&mut self, cursor: &mut TokenCursor) -> Result<Option<Types>, ParseError> {
+        if cursor.match_token(&Tokens::ARROW) {
+            Ok(Some(cursor.expect_type()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// `name: type`, `name: type = default` or `...name: list`. Parameters with a default
+    /// come after those without, and a rest parameter comes last.
+    fn parse_function_parameters(&mut self, cursor: &mut TokenCursor) -> Result<Vec<FunctionParameter>, ParseError> {
+        let mut params: Vec<FunctionParameter> = Vec::new();
 
         
         if matches!(cursor.current_token(), Some(Tokens::RPAREN)) {
-            return Some(params);
+            return Ok(params);
         }
 
         loop {  
-            let param_name = match cursor.consume_token()? {
-                Tokens::IDENTIFIER(name) => name.clone(),
-                Tokens::SELF => "self".to_string(),
-                _ => return None,
+            let start_span = cursor.current_span();
+            let rest = cursor.match_token(&Tokens::ELLIPSIS);
+            let param_name = if !rest && cursor.match_token(&Tokens::SELF) {
+                "self".to_string()
+            } else {
+                cursor.expect_identifier()?
             };
             if param_name == "self" {
                 params.push(FunctionParameter {
                     name: param_name,
                     data_type: Types::STRING,
+                    default: None,
+                    rest: false,
                 });
             } else {
                 
-                if !cursor.match_token(&Tokens::COLON) {
-                    return None;
-                }
+                cursor.expect_token(&Tokens::COLON)?;
+
+                let param_type = cursor.expect_type()?;
+                let default = if cursor.match_token(&Tokens::EQUALS) {
+                    Some(self.parse_expression(cursor)?)
+                } else {
+                    None
+                };
 
-                let param_type = match cursor.consume_token()? {
-                    Tokens::TYPE(t) => t.clone(),
-                    _ => return None,
+                let problem = if let Some(previous) = params.last().filter(|param| param.rest) {
+                    Some(format!("parameter '{}' follows rest parameter '...{}'", param_name, previous.name))
+                } else if rest && param_type != Types::LIST {
+                    Some(format!("rest parameter '...{}' must be a list", param_name))
+                } else if rest && default.is_some() {
+                    Some(format!("rest parameter '...{}' can't have a default", param_name))
+                } else if !rest && default.is_none() && params.iter().any(|param| param.default.is_some()) {
+                    Some(format!("parameter '{}' without a default follows one with a default", param_name))
+                } else {
+                    None
                 };
+                if let Some(message) = problem {
+                    return Err(ParseError { message, span: start_span.to(cursor.previous_span()) });
+                }
 
                 params.push(FunctionParameter {
                     name: param_name,
                     data_type: param_type,
+                    default,
+                    rest,
                 });
             }
 
@@ -619,10 +658,11 @@ impl ASTParser {
             }
         }
 
-        Some(params)
+        Ok(params)
     }
 
-    fn parse_return_statement(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    fn parse_return_statement(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let start_span = cursor.current_span();
         cursor.expect_token(&Tokens::RETURN)?; 
 
         
@@ -634,160 +674,113 @@ impl ASTParser {
             Some(self.parse_expression(cursor)?)
         };
 
-        Some(Statement::Return { value })
+        Ok(Statement::Return { value, span: start_span.to(cursor.previous_span()) })
     }
 
-    fn parse_class_declaration(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
-        if !cursor.match_token(&Tokens::CLASS) {
-            return None;
-        }
+    fn parse_class_declaration(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let start_span = cursor.current_span();
+        cursor.expect_token(&Tokens::CLASS)?;
 
-        let class_name = match cursor.consume_token() {
-            Some(Tokens::IDENTIFIER(name)) => name.clone(),
-            _ => return None,
-        };
+        let class_name = cursor.expect_identifier()?;
         
-        if !cursor.match_token(&Tokens::LBRACE) {
-            return None;
-        }
+        cursor.expect_token(&Tokens::LBRACE)?;
         
-        if !cursor.match_token(&Tokens::PUBLIC) {
-            return None;
-        }
+        cursor.expect_token(&Tokens::PUBLIC)?;
         
-        if !cursor.match_token(&Tokens::LBRACE) {
-            return None;
-        }
+        cursor.expect_token(&Tokens::LBRACE)?;
         
         let mut fields = HashMap::new();
         
         
         while !matches!(cursor.current_token(), Some(Tokens::RBRACE) | None) {
-            let field_name = match cursor.consume_token() {
-                Some(Tokens::IDENTIFIER(n)) => n.clone(),
-                _ => return None,
-            };
+            let field_span = cursor.current_span();
+            let field_name = cursor.expect_identifier()?;
 
-            if !cursor.match_token(&Tokens::COLON) {
-                return None;
-            }
+            cursor.expect_token(&Tokens::COLON)?;
             
-            let field_type = match cursor.consume_token() {
-                Some(Tokens::TYPE(t)) => t.clone(),
-                _ => return None,
-            };
+            let field_type = cursor.expect_type()?;
             
             fields.insert(field_name.clone(), Statement::ClassAttribute {
                 name: field_name,
                 data_type: field_type,
+                span: field_span.to(cursor.previous_span()),
             });
         }
         
-        if !cursor.match_token(&Tokens::RBRACE) {
-            return None;
-        }
+        cursor.expect_token(&Tokens::RBRACE)?;
 
         
         if matches!(cursor.current_token(), Some(Tokens::PUBLIC)) {
             cursor.consume_token();
             
-            if !cursor.match_token(&Tokens::LBRACE) {
-                return None;
-            }
+            cursor.expect_token(&Tokens::LBRACE)?;
             
             
             while !matches!(cursor.current_token(), Some(Tokens::RBRACE) | None) {
-                if !cursor.match_token(&Tokens::FN) {
-                    return None;
-                }
+                let method_span = cursor.current_span();
+                cursor.expect_token(&Tokens::FN)?;
 
-                let method_name = match cursor.consume_token() {
-                    Some(Tokens::IDENTIFIER(n)) => n.clone(),
-                    _ => return None,
-                };
+                let method_name = cursor.expect_identifier()?;
 
-                if !cursor.match_token(&Tokens::LPAREN) {
-                    return None;
-                }
+                cursor.expect_token(&Tokens::LPAREN)?;
 
-                let params = self.parse_function_parameters(cursor);
-                if params.is_none() {
-                    return None;
-                }
-                let params = params.unwrap();
+                let params = self.parse_function_parameters(cursor)?;
                 
-                if !cursor.match_token(&Tokens::RPAREN) {
-                    return None;
-                }
+                cursor.expect_token(&Tokens::RPAREN)?;
 
-                if !cursor.match_token(&Tokens::LBRACE) {
-                    return None;
-                }
+                let return_type = self.parse_return_type(cursor)?;
 
-                let body = self.parse_block_body(cursor);
-                if body.is_none() {
-                    return None;
-                }
-                let body = body.unwrap();
+                cursor.expect_token(&Tokens::LBRACE)?;
 
-                if !cursor.match_token(&Tokens::RBRACE) {
-                    return None;
-                }
+                let body = self.parse_function_body(cursor)?;
+
+                cursor.expect_token(&Tokens::RBRACE)?;
 
                 fields.insert(method_name.clone(), Statement::FunctionDeclaration {
                     name: method_name.clone(),
                     params,
+                    return_type,
                     body,
+                    span: method_span.to(cursor.previous_span()),
                 });
             }
             
-            if !cursor.match_token(&Tokens::RBRACE) {
-                return None;
-            }
+            cursor.expect_token(&Tokens::RBRACE)?;
         }
 
-        if !cursor.match_token(&Tokens::RBRACE) {
-            return None;
-        }
+        cursor.expect_token(&Tokens::RBRACE)?;
         
-        Some(Statement::ClassMeta {
+        Ok(Statement::ClassMeta {
             name: class_name,
             fields,
+            span: start_span.to(cursor.previous_span()),
         })
     }
 
-    fn parse_variable_declaration(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    fn parse_variable_declaration(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let start_span = cursor.current_span();
         cursor.expect_token(&Tokens::LET)?;
 
-        let name = match cursor.consume_token()? {
-            Tokens::IDENTIFIER(n) => n.clone(),
-            _ => return None,
-        };
+        let name = cursor.expect_identifier()?;
 
         let mut data_type = None;
         if cursor.match_token(&Tokens::COLON) {
-            data_type = match cursor.consume_token()? {
-                Tokens::TYPE(t) => Some(t.clone()),
-                _ => return None,
-            };
+            data_type = Some(cursor.expect_type()?);
         }
 
         cursor.expect_token(&Tokens::EQUALS)?;
         let value_expr = self.parse_expression(cursor)?;
 
-        
-        if data_type.is_none() {
-            data_type = Some(Types::STRING);
-        }
-
-        Some(Statement::VariableDeclaration {
+        Ok(Statement::VariableDeclaration {
             name,
-            data_type: data_type.unwrap(),
+            data_type,
             value: value_expr,
+            span: start_span.to(cursor.previous_span()),
         })
     }
 
-    fn parse_conditional_statement(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    fn parse_conditional_statement(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let start_span = cursor.current_span();
         cursor.expect_token(&Tokens::IF)?;
         cursor.expect_token(&Tokens::LPAREN)?;
 
@@ -801,30 +794,58 @@ impl ASTParser {
         cursor.expect_token(&Tokens::RBRACE)?;
 
         let else_branch = if cursor.match_token(&Tokens::ELSE) {
-            cursor.expect_token(&Tokens::LBRACE)?;
-            let else_statements = self.parse_block_body(cursor)?;
-            cursor.expect_token(&Tokens::RBRACE)?;
-            Some(else_statements)
+            if matches!(cursor.current_token(), Some(Tokens::IF)) {
+                // `else if` is an `else` whose only statement is the next conditional.
+                Some(vec![self.parse_conditional_statement(cursor)?])
+            } else {
+                cursor.expect_token(&Tokens::LBRACE)?;
+                let else_statements = self.parse_block_body(cursor)?;
+                cursor.expect_token(&Tokens::RBRACE)?;
+                Some(else_statements)
+            }
         } else {
             None
         };
         
-        Some(Statement::Conditional {
+        Ok(Statement::Conditional {
             condition,
             then_branch,
             else_branch,
+            span: start_span.to(cursor.previous_span()),
         })
     }
 
-    fn parse_for_loop(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    /// Parses `for x in iterable { ... }` or the range form `for x in ./[start,end,step] { ... }`.
+    /// The `x in ...` part may be wrapped in parentheses.
+    fn parse_for_loop(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let start_span = cursor.current_span();
         cursor.expect_token(&Tokens::FOR)?; 
+        let parenthesized = cursor.match_token(&Tokens::LPAREN);
         
-        let variable = match cursor.consume_token()? {
-            Tokens::IDENTIFIER(n) => n.clone(),
-            _ => return None,
-        };
+        let variable = cursor.expect_identifier()?;
 
         cursor.expect_token(&Tokens::IN)?; 
+
+        if !matches!(cursor.current_token(), Some(Tokens::DOT)) {
+            let enclosing = std::mem::replace(&mut self.in_loop_header, !parenthesized);
+            let iterable = self.parse_expression(cursor);
+            self.in_loop_header = enclosing;
+            let iterable = iterable?;
+            if parenthesized {
+                cursor.expect_token(&Tokens::RPAREN)?;
+            }
+            cursor.expect_token(&Tokens::LBRACE)?;
+            let body = self.parse_loop_body(cursor)?;
+            cursor.expect_token(&Tokens::RBRACE)?;
+
+            return Ok(Statement::ForEach {
+                variable,
+                iterable,
+                body,
+                span: start_span.to(cursor.previous_span()),
+            });
+        }
+
         cursor.expect_token(&Tokens::DOT)?;
         cursor.expect_token(&Tokens::SLASH)?;
         cursor.expect_token(&Tokens::LSQRBRAC)?;
@@ -838,22 +859,27 @@ impl ASTParser {
         let step = self.parse_expression(cursor)?;
 
         cursor.expect_token(&Tokens::RSQRBRAC)?;
+        if parenthesized {
+            cursor.expect_token(&Tokens::RPAREN)?;
+        }
         cursor.expect_token(&Tokens::LBRACE)?;
 
-        let body = self.parse_block_body(cursor)?;
+        let body = self.parse_loop_body(cursor)?;
 
         cursor.expect_token(&Tokens::RBRACE)?;
         
-        Some(Statement::ForLoop {
+        Ok(Statement::ForLoop {
             variable,
             start,
             end,
             step,
             body,
+            span: start_span.to(cursor.previous_span()),
         })
     }
 
-    fn parse_while_loop(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    fn parse_while_loop(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let start_span = cursor.current_span();
         cursor.expect_token(&Tokens::WHILE)?;
         cursor.expect_token(&Tokens::LPAREN)?;
 
@@ -862,210 +888,289 @@ impl ASTParser {
         cursor.expect_token(&Tokens::RPAREN)?;
         cursor.expect_token(&Tokens::LBRACE)?;
 
-        let body = self.parse_block_body(cursor)?;
+        let body = self.parse_loop_body(cursor)?;
 
         cursor.expect_token(&Tokens::RBRACE)?;
 
-        Some(Statement::WhileLoop {
+        Ok(Statement::WhileLoop {
             condition,
             body,
+            span: start_span.to(cursor.previous_span()),
         })
     }
 
-    fn parse_assignment_or_expression(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    fn parse_assignment_or_expression(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
         
-        let start_pos = cursor.position;
+        let start_span = cursor.current_span();
+        let expr = self.parse_expression(cursor)?;
         
+        let compound_operator = match cursor.current_token() {
+            Some(Tokens::EQUALS) => None,
+            Some(Tokens::PLUS_EQUALS) => Some(ArithmeticOperator::Add),
+            Some(Tokens::MINUS_EQUALS) => Some(ArithmeticOperator::Subtract),
+            Some(Tokens::STAR_EQUALS) => Some(ArithmeticOperator::Multiply),
+            Some(Tokens::SLASH_EQUALS) => Some(ArithmeticOperator::Divide),
+            Some(Tokens::MODULO_EQUALS) => Some(ArithmeticOperator::Modulo),
+            _ => {
+                let span = expr.span();
+                return Ok(Statement::ExpressionStatement { expression: expr, span });
+            },
+        };
         
-        if let Some(expr) = self.parse_expression(cursor) {
-            
-            if cursor.match_token(&Tokens::EQUALS) {
-                let value = self.parse_expression(cursor)?;
-                
-                
-                match expr {
-                    AstExpressions::MemberAccess { object, member } => {
-                        Some(Statement::MemberAssignment {
-                            object: *object,
-                            member,
-                            value,
-                        })
-                    },
-                    AstExpressions::Variable { name } => {
-                        Some(Statement::Assignment { name, value })
-                    },
-                    _ => {
-                        None
-                    }
-                }
-            } else {
-                
-                Some(Statement::ExpressionStatement { expression: expr })
-            }
-        } else {
-            
-            cursor.position = start_pos;
-            
-            let name = match cursor.consume_token()? {
-                Tokens::IDENTIFIER(n) => n.clone(),
-                _ => return None,
+        let target_span = expr.span();
+        cursor.consume_token();
+        let value = self.parse_expression(cursor)?;
+        let span = start_span.to(cursor.previous_span());
+
+        if let Some(operator) = compound_operator {
+            return match expr {
+                AstExpressions::Variable { .. } | AstExpressions::MemberAccess { .. } | AstExpressions::Index { .. } => {
+                    Ok(Statement::CompoundAssignment { target: expr, operator, value, span })
+                },
+                _ => Err(ParseError {
+                    message: "invalid assignment target".to_string(),
+                    span: target_span,
+                }),
             };
+        }
 
-            if cursor.match_token(&Tokens::EQUALS) {
-                let value = self.parse_expression(cursor)?;
-                Some(Statement::Assignment { name, value })
-            } else {
-                
-                cursor.position -= 1;
-                let expr = self.parse_expression(cursor)?;
-                Some(Statement::ExpressionStatement { expression: expr })
-            }
+        match expr {
+            AstExpressions::MemberAccess { object, member, .. } => {
+                Ok(Statement::MemberAssignment {
+                    object: *object,
+                    member,
+                    value,
+                    span,
+                })
+            },
+            AstExpressions::Variable { name, .. } => {
+                Ok(Statement::Assignment { name, value, span })
+            },
+            AstExpressions::Index { object, index, .. } => {
+                Ok(Statement::IndexAssignment {
+                    object: *object,
+                    index: *index,
+                    value,
+                    span,
+                })
+            },
+            _ => Err(ParseError {
+                message: "invalid assignment target".to_string(),
+                span: target_span,
+            }),
         }
     }
 
-    fn parse_block_statement(&mut self, cursor: &mut TokenCursor) -> Option<Statement> {
+    fn parse_block_statement(&mut self, cursor: &mut TokenCursor) -> Result<Statement, ParseError> {
+        let start_span = cursor.current_span();
         cursor.expect_token(&Tokens::LBRACE)?;
         let statements = self.parse_block_body(cursor)?;
         cursor.expect_token(&Tokens::RBRACE)?;
-        Some(Statement::Block(statements))
+        Ok(Statement::Block { statements, span: start_span.to(cursor.previous_span()) })
     }
 
-    fn parse_block_body(&mut self, cursor: &mut TokenCursor) -> Option<Vec<Statement>> {
+    fn parse_block_body(&mut self, cursor: &mut TokenCursor) -> Result<Vec<Statement>, ParseError> {
         let mut statements = Vec::new();
 
         while !cursor.is_at_end() && !matches!(cursor.current_token(), Some(Tokens::RBRACE)) {
-            if let Some(stmt) = self.parse_statement(cursor) {
-                statements.push(stmt);
-            } else {
-                cursor.consume_token(); 
+            let statement_start = cursor.position;
+            match self.parse_statement(cursor) {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize(cursor, statement_start);
+                }
             }
         }
 
-        Some(statements)
+        Ok(statements)
     }
 
     
-    fn parse_expression(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
+    fn parse_expression(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
         self.parse_logical_or(cursor)
     }
 
-    fn parse_logical_or(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
+    fn parse_logical_or(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
         let mut left = self.parse_logical_and(cursor)?;
 
         while cursor.match_token(&Tokens::OR) {
             let right = self.parse_logical_and(cursor)?;
+            let span = left.span().to(right.span());
             left = AstExpressions::LogicalOperation {
                 left: Box::new(left),
                 operator: LogicalOperator::Or,
                 right: Box::new(right),
+                span,
             };
         }
 
-        Some(left)
+        Ok(left)
     }
 
-    fn parse_logical_and(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
+    fn parse_logical_and(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
         let mut left = self.parse_equality(cursor)?;
 
         while cursor.match_token(&Tokens::AND) {
             let right = self.parse_equality(cursor)?;
+            let span = left.span().to(right.span());
             left = AstExpressions::LogicalOperation {
                 left: Box::new(left),
                 operator: LogicalOperator::And,
                 right: Box::new(right),
+                span,
             };
         }
 
-        Some(left)
+        Ok(left)
     }
 
-    fn parse_equality(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
+    fn parse_equality(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
         let mut left = self.parse_comparison(cursor)?;
 
         while let Some(operator) = self.match_comparison_operator(cursor) {
             let right = self.parse_comparison(cursor)?;
+            let span = left.span().to(right.span());
             left = AstExpressions::ComparisonOperation {
                 left: Box::new(left),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
 
-        Some(left)
+        Ok(left)
     }
 
-    fn parse_comparison(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
-        self.parse_term(cursor)
+    /// Bitwise operators bind tighter than comparisons, as in Python: `x & 1 == 0` is
+    /// `(x & 1) == 0`. From loosest to tightest: `|`, `^`, `&`, shifts, `+ -`, `* / ~/ %`,
+    /// unary operators, then `**`.
+    fn parse_comparison(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        self.parse_bit_or(cursor)
     }
 
-    fn parse_term(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
-        let mut left = self.parse_factor(cursor)?;
+    fn parse_bit_or(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        self.parse_binary_operations(cursor, &[Tokens::PIPE], Self::parse_bit_xor)
+    }
 
-        while let Some(operator) = self.match_arithmetic_operator(cursor, &[Tokens::PLUS, Tokens::MINUS]) {
-            let right = self.parse_factor(cursor)?;
-            left = AstExpressions::BinaryOperation {
-                left: Box::new(left),
-                operator,
-                right: Box::new(right),
-            };
-        }
+    fn parse_bit_xor(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        self.parse_binary_operations(cursor, &[Tokens::CARET], Self::parse_bit_and)
+    }
+
+    fn parse_bit_and(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        self.parse_binary_operations(cursor, &[Tokens::AMPERSAND], Self::parse_shift)
+    }
+
+    fn parse_shift(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        self.parse_binary_operations(cursor, &[Tokens::LESS_LESS, Tokens::GREATER_GREATER], Self::parse_term)
+    }
 
-        Some(left)
+    fn parse_term(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        self.parse_binary_operations(cursor, &[Tokens::PLUS, Tokens::MINUS], Self::parse_factor)
     }
 
-    fn parse_factor(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
-        let mut left = self.parse_unary(cursor)?;
+    fn parse_factor(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        self.parse_binary_operations(cursor, &[Tokens::STAR, Tokens::SLASH, Tokens::TILDE_SLASH, Tokens::MODULO], Self::parse_unary)
+    }
 
-        while let Some(operator) = self.match_arithmetic_operator(cursor, &[Tokens::STAR, Tokens::SLASH, Tokens::MODULO]) {
-            let right = self.parse_unary(cursor)?;
+    /// Parses a left-associative chain of `operand` separated by any of `operators`.
+    fn parse_binary_operations(
+        &mut self,
+        cursor: &mut TokenCursor,
+        operators: &[Tokens],
+        operand: fn(&mut Self, &mut TokenCursor) -> Result<AstExpressions, ParseError>,
+    ) -> Result<AstExpressions, ParseError> {
+        let mut left = operand(self, cursor)?;
+
+        while let Some(operator) = self.match_arithmetic_operator(cursor, operators) {
+            let right = operand(self, cursor)?;
+            let span = left.span().to(right.span());
             left = AstExpressions::BinaryOperation {
                 left: Box::new(left),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
 
-        Some(left)
+        Ok(left)
     }
 
-    fn parse_unary(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
+    fn parse_unary(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        let start_span = cursor.current_span();
         if let Some(operator) = self.match_unary_operator(cursor) {
             let operand = self.parse_unary(cursor)?;
-            Some(AstExpressions::UnaryOperation {
+            let span = start_span.to(operand.span());
+            Ok(AstExpressions::UnaryOperation {
                 operator,
                 operand: Box::new(operand),
+                span,
             })
         } else {
-            self.parse_primary(cursor)
+            self.parse_power(cursor)
+        }
+    }
+
+    /// `**` is right-associative and binds tighter than a unary operator on its left, so
+    /// `-2 ** 2` is `-(2 ** 2)` and `2 ** 3 ** 2` is `2 ** 9`.
+    fn parse_power(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        let base = self.parse_primary(cursor)?;
+
+        if !cursor.match_token(&Tokens::STAR_STAR) {
+            return Ok(base);
         }
+        let exponent = self.parse_unary(cursor)?;
+        let span = base.span().to(exponent.span());
+        Ok(AstExpressions::BinaryOperation {
+            left: Box::new(base),
+            operator: ArithmeticOperator::Power,
+            right: Box::new(exponent),
+            span,
+        })
     }
 
-    fn parse_primary(&mut self, cursor: &mut TokenCursor) -> Option<AstExpressions> {
+    fn parse_primary(&mut self, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        let start_span = cursor.current_span();
         match cursor.current_token() {
             Some(Tokens::VALUE(value)) => {
                 let val = value.clone();
                 cursor.consume_token();
-                Some(AstExpressions::Value { value: val })
+                self.parse_member_access_or_call(AstExpressions::Value { value: val, span: start_span }, cursor)
             },
             Some(Tokens::IDENTIFIER(name)) => {
                 let name = name.clone();
                 cursor.consume_token();
                 
-                
-                self.parse_member_access_or_call(AstExpressions::Variable { name }, cursor)
+                if self.at_instance_literal(cursor) {
+                    let literal = self.parse_instance_literal(name, start_span, cursor)?;
+                    return self.parse_member_access_or_call(literal, cursor);
+                }
+                self.parse_member_access_or_call(AstExpressions::Variable { name, span: start_span }, cursor)
+            },
+            // `map` names the map type in annotations. A type can't start an expression, so
+            // here it is the `map` builtin.
+            Some(Tokens::TYPE(Types::MAP)) => {
+                cursor.consume_token();
+                let builtin = AstExpressions::Variable { name: "map".to_string(), span: start_span };
+                self.parse_member_access_or_call(builtin, cursor)
             },
             Some(Tokens::SELF) => {
                 cursor.consume_token();
-                let self_expr = AstExpressions::Variable { name: "self".to_string() };
+                let self_expr = AstExpressions::Variable { name: "self".to_string(), span: start_span };
                 
                 
                 self.parse_member_access_or_call(self_expr, cursor)
             },
+            Some(Tokens::FN) => {
+                let lambda = self.parse_lambda(cursor)?;
+                self.parse_member_access_or_call(lambda, cursor)
+            },
             Some(Tokens::LPAREN) => {
                 cursor.consume_token();
                 let expr = self.parse_expression(cursor)?;
                 cursor.expect_token(&Tokens::RPAREN)?;
-                Some(AstExpressions::Grouping { expression: Box::new(expr) })
+                let grouping = AstExpressions::Grouping { expression: Box::new(expr), span: start_span.to(cursor.previous_span()) };
+                self.parse_member_access_or_call(grouping, cursor)
             },
             Some(Tokens::LSQRBRAC) => {
                 cursor.consume_token();
@@ -1082,73 +1187,187 @@ impl ASTParser {
                     }
                 }
                 cursor.expect_token(&Tokens::RSQRBRAC)?;
-                Some(AstExpressions::ListLiteral { elements })
+                let list = AstExpressions::ListLiteral { elements, span: start_span.to(cursor.previous_span()) };
+                self.parse_member_access_or_call(list, cursor)
             },
-            _ => None,
+            // A `{` that starts a statement is a block; here, inside an expression, it can only
+            // be a map literal.
+            Some(Tokens::LBRACE) => {
+                cursor.consume_token();
+                let mut entries = Vec::new();
+
+                if !matches!(cursor.current_token(), Some(Tokens::RBRACE)) {
+                    loop {
+                        let key = self.parse_expression(cursor)?;
+                        cursor.expect_token(&Tokens::COLON)?;
+                        let value = self.parse_expression(cursor)?;
+                        entries.push((key, value));
+                        if !cursor.match_token(&Tokens::COMMA) {
+                            break;
+                        }
+                    }
+                }
+                cursor.expect_token(&Tokens::RBRACE)?;
+                let map = AstExpressions::MapLiteral { entries, span: start_span.to(cursor.previous_span()) };
+                self.parse_member_access_or_call(map, cursor)
+            },
+            _ => Err(cursor.error_expected("an expression")),
         }
     }
     
-    fn parse_member_access_or_call(&mut self, mut expr: AstExpressions, cursor: &mut TokenCursor) -> Option<AstExpressions> {
+    /// After a name, whether a `{` opens an instance literal: `{ field: ...` or `{ }`. Anything
+    /// else after the brace, such as a statement, means the name ends the expression and a
+    /// block follows.
+    fn at_instance_literal(&self, cursor: &TokenCursor) -> bool {
+        if !matches!(cursor.current_token(), Some(Tokens::LBRACE)) {
+            return false;
+        }
+        match cursor.peek_token(1) {
+            Some(Tokens::IDENTIFIER(_)) => matches!(cursor.peek_token(2), Some(Tokens::COLON)),
+            Some(Tokens::RBRACE) => !self.in_loop_header,
+            _ => false,
+        }
+    }
+
+    /// `Name { field: value, ... }`, with the cursor on the `{`.
+    fn parse_instance_literal(&mut self, class_name: String, start_span: Span, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        cursor.expect_token(&Tokens::LBRACE)?;
+        let mut fields: Vec<(String, AstExpressions)> = Vec::new();
+        // Reported once the whole literal is read, so parsing resumes after it.
+        let mut repeated = None;
+
+        if !matches!(cursor.current_token(), Some(Tokens::RBRACE)) {
+            loop {
+                let field_span = cursor.current_span();
+                let field = cursor.expect_identifier()?;
+                if repeated.is_none() && fields.iter().any(|(name, _)| *name == field) {
+                    repeated = Some(ParseError {
+                        message: format!("field '{}' is given twice", field),
+                        span: field_span,
+                    });
+                }
+                cursor.expect_token(&Tokens::COLON)?;
+                fields.push((field, self.parse_expression(cursor)?));
+                if !cursor.match_token(&Tokens::COMMA) {
+                    break;
+                }
+            }
+        }
+        cursor.expect_token(&Tokens::RBRACE)?;
+        if let Some(error) = repeated {
+            return Err(error);
+        }
+
+        Ok(AstExpressions::InstanceLiteral { class_name, fields, span: start_span.to(cursor.previous_span()) })
+    }
+
+    fn parse_member_access_or_call(&mut self, mut expr: AstExpressions, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
         loop {
             match cursor.current_token() {
                 Some(Tokens::DOT) => {
                     cursor.consume_token(); 
                     
-                    let member_name = match cursor.consume_token()? {
-                        Tokens::IDENTIFIER(name) => name.clone(),
-                        _ => return None,
-                    };  
+                    let member_name = cursor.expect_identifier()?;  
                     
                     if matches!(cursor.current_token(), Some(Tokens::LPAREN)) {
                         cursor.consume_token(); 
-                        let arguments = self.parse_function_arguments(cursor)?;
+                        let (arguments, keyword_arguments) = self.parse_function_arguments(cursor)?;
                         cursor.expect_token(&Tokens::RPAREN)?;
                         
+                        let span = expr.span().to(cursor.previous_span());
                         expr = AstExpressions::MethodCall {
                             object: Box::new(expr),
                             method: member_name,
                             arguments,
+                            keyword_arguments,
+                            span,
                         };
                     } else {
                         
+                        let span = expr.span().to(cursor.previous_span());
                         expr = AstExpressions::MemberAccess {
                             object: Box::new(expr),
                             member: member_name,
+                            span,
                         };
                     }
                 },
+                Some(Tokens::LSQRBRAC) => {
+                    cursor.consume_token();
+                    expr = self.parse_index_or_slice(expr, cursor)?;
+                },
                 Some(Tokens::LPAREN) => {
-                    
                     cursor.consume_token(); 
-                    let arguments = self.parse_function_arguments(cursor)?;
+                    let (arguments, keyword_arguments) = self.parse_function_arguments(cursor)?;
                     cursor.expect_token(&Tokens::RPAREN)?;
                     
-                    
-                    if let AstExpressions::Variable { name } = expr {
-                        expr = AstExpressions::FunctionCall {
-                            name,
-                            arguments,
-                        };
-                    } else {
-                        return None; 
-                    }
+                    let span = expr.span().to(cursor.previous_span());
+                    expr = match expr {
+                        AstExpressions::Variable { name, .. } if name != "self" => {
+                            AstExpressions::FunctionCall { name, arguments, keyword_arguments, span }
+                        },
+                        callee => AstExpressions::Call { callee: Box::new(callee), arguments, keyword_arguments, span },
+                    };
                 },
                 _ => break,
             }
         }
         
-        Some(expr)
+        Ok(expr)
     }
 
-    fn parse_function_arguments(&mut self, cursor: &mut TokenCursor) -> Option<Vec<AstExpressions>> {
+    /// Parses what follows `[` after an expression: `index]`, `start:end]`, `start:]`, `:end]` or `:]`.
+    fn parse_index_or_slice(&mut self, object: AstExpressions, cursor: &mut TokenCursor) -> Result<AstExpressions, ParseError> {
+        let start = if matches!(cursor.current_token(), Some(Tokens::COLON)) {
+            None
+        } else {
+            Some(Box::new(self.parse_expression(cursor)?))
+        };
+
+        if !cursor.match_token(&Tokens::COLON) {
+            cursor.expect_token(&Tokens::RSQRBRAC)?;
+            let span = object.span().to(cursor.previous_span());
+            return match start {
+                Some(index) => Ok(AstExpressions::Index { object: Box::new(object), index, span }),
+                None => Err(cursor.error_expected("an index")),
+            };
+        }
+
+        let end = if matches!(cursor.current_token(), Some(Tokens::RSQRBRAC)) {
+            None
+        } else {
+            Some(Box::new(self.parse_expression(cursor)?))
+        };
+        cursor.expect_token(&Tokens::RSQRBRAC)?;
+
+        let span = object.span().to(cursor.previous_span());
+        Ok(AstExpressions::Slice { object: Box::new(object), start, end, span })
+    }
+
+    /// Positional arguments, then `name: value` keyword arguments.
+    fn parse_function_arguments(&mut self, cursor: &mut TokenCursor) -> Result<(Vec<AstExpressions>, Vec<KeywordArgument>), ParseError> {
         let mut args = Vec::new();
+        let mut keyword_args = Vec::new();
 
         if matches!(cursor.current_token(), Some(Tokens::RPAREN)) {
-            return Some(args);
+            return Ok((args, keyword_args));
         }
 
         loop {
-            args.push(self.parse_expression(cursor)?);
+            if matches!(cursor.peek_token(1), Some(Tokens::COLON)) {
+                let name = cursor.expect_identifier()?;
+                cursor.consume_token();
+                keyword_args.push((name, self.parse_expression(cursor)?));
+            } else {
+                let argument = self.parse_expression(cursor)?;
+                if !keyword_args.is_empty() {
+                    return Err(ParseError {
+                        message: "positional argument after keyword arguments".to_string(),
+                        span: argument.span(),
+                    });
+                }
+                args.push(argument);
+            }
             if cursor.match_token(&Tokens::COMMA) {
                 continue;
             } else {
@@ -1156,7 +1375,7 @@ impl ASTParser {
             }
         }
 
-        Some(args)
+        Ok((args, keyword_args))
     }
 
     fn match_comparison_operator(&mut self, cursor: &mut TokenCursor) -> Option<ComparisonOperator> {
@@ -1203,6 +1422,12 @@ impl ASTParser {
                         Tokens::STAR => Some(ArithmeticOperator::Multiply),
                         Tokens::SLASH => Some(ArithmeticOperator::Divide),
                         Tokens::MODULO => Some(ArithmeticOperator::Modulo),
+                        Tokens::TILDE_SLASH => Some(ArithmeticOperator::FloorDivide),
+                        Tokens::AMPERSAND => Some(ArithmeticOperator::BitAnd),
+                        Tokens::PIPE => Some(ArithmeticOperator::BitOr),
+                        Tokens::CARET => Some(ArithmeticOperator::BitXor),
+                        Tokens::LESS_LESS => Some(ArithmeticOperator::ShiftLeft),
+                        Tokens::GREATER_GREATER => Some(ArithmeticOperator::ShiftRight),
                         _ => None,
                     };
                 }
@@ -1221,6 +1446,14 @@ impl ASTParser {
                 cursor.consume_token();
                 Some(ArithmeticOperator::Add)
             },
+            Some(Tokens::NOT) => {
+                cursor.consume_token();
+                Some(ArithmeticOperator::Not)
+            },
+            Some(Tokens::TILDE) => {
+                cursor.consume_token();
+                Some(ArithmeticOperator::BitNot)
+            },
             _ => None,
         }
     }
@@ -1,50 +1,94 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::sync::Mutex;
-use std::sync::OnceLock;
-use crate::tokenizer::DataHolder;
+use std::rc::Rc;
+use crate::tokenizer::Types;
+use crate::values::DataHolder;
 use crate::AstTree::Statement;
 
+/// One scope in a chain of scopes. Lookups that miss in this scope continue in `parent`,
+/// so a block sees the variables of the blocks around it and a function body sees the
+/// module globals, but never the locals of whoever called it.
 #[derive(Debug, Clone)]
 pub struct Environment {
     variables: HashMap<String, DataHolder>,
+    /// The type each variable in this scope was declared with, or inferred from its first value.
+    types: HashMap<String, Types>,
     classes: HashMap<String, Statement>,
+    parent: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
             variables: HashMap::new(),
+            types: HashMap::new(),
             classes: HashMap::new(),
+            parent: None,
         }
     }
 
+    /// A scope nested inside `parent`: a block, or the body of a function call.
+    pub fn new_child(parent: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            parent: Some(parent),
+            ..Environment::new()
+        }
+    }
+
+    /// Declares `name` in this scope, shadowing any binding in an enclosing scope. The
+    /// variable's type is inferred from `value`.
     pub fn set_variable(&mut self, name: String, value: DataHolder) {
+        let data_type = value.get_type();
+        self.declare_variable(name, value, data_type);
+    }
+
+    /// Declares `name` in this scope with an explicit type.
+    pub fn declare_variable(&mut self, name: String, value: DataHolder, data_type: Types) {
+        self.types.insert(name.clone(), data_type);
         self.variables.insert(name, value);
     }
 
-    pub fn get_variable(&self, name: &str) -> Option<&DataHolder> {
-        self.variables.get(name)
+    /// Rebinds the nearest existing `name`, searching outwards through every enclosing scope,
+    /// module globals included. Returns `false` when there is no such binding.
+    pub fn assign_variable(&mut self, name: &str, value: DataHolder) -> bool {
+        if let Some(slot) = self.variables.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &self.parent {
+            Some(parent) => parent.borrow_mut().assign_variable(name, value),
+            None => false,
+        }
+    }
+
+    pub fn get_variable(&self, name: &str) -> Option<DataHolder> {
+        match self.variables.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref()?.borrow().get_variable(name),
+        }
     }
 
-    pub fn get_all_variables(&self) -> &HashMap<String, DataHolder> {
-        &self.variables
+    /// The declared type of the nearest visible `name`.
+    pub fn get_variable_type(&self, name: &str) -> Option<Types> {
+        match self.types.get(name) {
+            Some(data_type) => Some(data_type.clone()),
+            None => self.parent.as_ref()?.borrow().get_variable_type(name),
+        }
     }
 
     pub fn set_class(&mut self, name: String, fields: Statement) {
         self.classes.insert(name, fields);
     }
 
-    pub fn get_class(&self, name: &str) -> Option<&Statement> {
-        self.classes.get(name)
+    pub fn get_class(&self, name: &str) -> Option<Statement> {
+        match self.classes.get(name) {
+            Some(class) => Some(class.clone()),
+            None => self.parent.as_ref()?.borrow().get_class(name),
+        }
     }
 
     pub fn is_class_meta_exists(&self, name: &str) -> bool {
         self.classes.contains_key(name)
+            || self.parent.as_ref().is_some_and(|parent| parent.borrow().is_class_meta_exists(name))
     }
 }
-
-static GLOBAL_ENV: OnceLock<Mutex<Environment>> = OnceLock::new();
-
-pub fn get_global_env() -> &'static Mutex<Environment> {
-    GLOBAL_ENV.get_or_init(|| Mutex::new(Environment::new()))
-}
@@ -1,7 +1,12 @@
 use std::{collections::HashMap, sync::{Mutex, OnceLock}};
-use crate::tokenizer::{Types, DataHolder};
+use crate::tokenizer::{ArithmeticOperator, Types};
+use crate::values::DataHolder;
+use crate::runtime::{Runtime, RuntimeError, MAX_RECURSION_LIMIT};
+use crate::Methods::{compare_values, unsortable_pair};
 
-type BuiltInFn = fn(Vec<DataHolder>) -> Option<DataHolder>;
+/// A native function. It gets the running `Runtime` so it can reach the program's output and
+/// call function values passed to it with `Runtime::call_value`.
+type BuiltInFn = fn(&mut Runtime, Vec<DataHolder>) -> Result<DataHolder, RuntimeError>;
 
 pub struct BuiltInFunction {
     function_map: HashMap<String, BuiltInFn>,
@@ -14,26 +19,33 @@ impl BuiltInFunction {
         function_map.insert("println".to_string(), println_fn as BuiltInFn);
         function_map.insert("len".to_string(), len_fn as BuiltInFn);
         function_map.insert("current_time".to_string(), current_time_fn as BuiltInFn);
+        function_map.insert("set_recursion_limit".to_string(), set_recursion_limit_fn as BuiltInFn);
         function_map.insert("to_string".to_string(), to_string_fn as BuiltInFn);
         function_map.insert("parse_int".to_string(), parse_int_fn as BuiltInFn);
+        function_map.insert("range".to_string(), range_fn as BuiltInFn);
+        function_map.insert("to_i32".to_string(), to_i32_fn as BuiltInFn);
+        function_map.insert("to_i64".to_string(), to_i64_fn as BuiltInFn);
+        function_map.insert("to_f32".to_string(), to_f32_fn as BuiltInFn);
+        function_map.insert("to_f64".to_string(), to_f64_fn as BuiltInFn);
+        function_map.insert("map".to_string(), map_fn as BuiltInFn);
+        function_map.insert("filter".to_string(), filter_fn as BuiltInFn);
+        function_map.insert("reduce".to_string(), reduce_fn as BuiltInFn);
+        function_map.insert("sort_by".to_string(), sort_by_fn as BuiltInFn);
+        function_map.insert("any".to_string(), any_fn as BuiltInFn);
+        function_map.insert("all".to_string(), all_fn as BuiltInFn);
+        function_map.insert("zip".to_string(), zip_fn as BuiltInFn);
+        function_map.insert("enumerate".to_string(), enumerate_fn as BuiltInFn);
+        function_map.insert("sum".to_string(), sum_fn as BuiltInFn);
 
         BuiltInFunction { function_map }
     }
 
-    pub fn call(&self, name: &str, args: Vec<DataHolder>) -> Option<DataHolder> {
-        if let Some(func) = self.function_map.get(name) {
-            func(args)
-        } else {
-            None
-        }
-    }
-
     pub fn has_function(&self, name: &str) -> bool {
         self.function_map.contains_key(name)
     }
 
-    pub fn get_function_names(&self) -> Vec<String> {
-        self.function_map.keys().cloned().collect()
+    pub fn get(&self, name: &str) -> Option<BuiltInFn> {
+        self.function_map.get(name).copied()
     }
 }
 
@@ -43,124 +55,396 @@ pub fn get_built_in_functions() -> &'static Mutex<BuiltInFunction> {
     BUILT_IN_FUNCTIONS.get_or_init(|| Mutex::new(BuiltInFunction::new()))
 }
 
-fn print_fn(args: Vec<DataHolder>) -> Option<DataHolder> {
-    for (i, arg) in args.iter().enumerate() {
-        if i > 0 { print!(" "); }
-        match arg {
-            DataHolder::INTEGER32(n) => print!("{}", n),
-            DataHolder::INTEGER64(n) => print!("{}", n),
-            DataHolder::FLOAT32(n) => print!("{}", n),
-            DataHolder::FLOAT64(n) => print!("{}", n),
-            DataHolder::STRING(s) => print!("{}", s),
-            DataHolder::BOOLEAN(b) => print!("{}", b),
-            DataHolder::LIST(list) => {
-                print!("[");
-                for (j, item) in list.iter().enumerate() {
-                    if j > 0 { print!(", "); }
-                    match item {
-                        DataHolder::STRING(s) => print!("\"{}\"", s),
-                        other => match other {
-                            DataHolder::INTEGER32(n) => print!("{}", n),
-                            DataHolder::INTEGER64(n) => print!("{}", n),
-                            DataHolder::FLOAT32(n) => print!("{}", n),
-                            DataHolder::FLOAT64(n) => print!("{}", n),
-                            DataHolder::BOOLEAN(b) => print!("{}", b),
-                            _ => print!("{:?}", other),
-                        }
-                    }
-                }
-                print!("]");
-            },
-            _ => print!("{:?}", arg),
-        }
+/// Formats a value the way `print` shows it. Strings inside a list are quoted; a string on
+/// its own is not.
+pub fn display_value(value: &DataHolder) -> String {
+    match value {
+        DataHolder::INTEGER32(n) => n.to_string(),
+        DataHolder::INTEGER64(n) => n.to_string(),
+        DataHolder::FLOAT32(n) => display_float(n.to_string()),
+        DataHolder::FLOAT64(n) => display_float(n.to_string()),
+        DataHolder::STRING(s) => s.clone(),
+        DataHolder::BOOLEAN(b) => b.to_string(),
+        DataHolder::LIST(list) => {
+            let items: Vec<String> = list.iter().map(display_element).collect();
+            format!("[{}]", items.join(", "))
+        },
+        DataHolder::MAP(entries) => {
+            let items: Vec<String> = entries.iter()
+                .map(|(key, value)| format!("{}: {}", display_element(key), display_element(value)))
+                .collect();
+            format!("{{{}}}", items.join(", "))
+        },
+        DataHolder::CLASSINSTANCE(instance) => format!("<{} instance>", instance.borrow().class_name),
+        DataHolder::FUNCTION(callable) => format!("<fn {}>", callable.name()),
+    }
+}
+
+/// Whole floats keep a `.0` so they read as floats: `3.0`, not `3`.
+fn display_float(text: String) -> String {
+    if text.contains(['.', 'i', 'N']) { text } else { text + ".0" }
+}
+
+/// Formats a value shown inside a list or map, where strings are quoted.
+pub fn display_element(value: &DataHolder) -> String {
+    match value {
+        DataHolder::STRING(s) => format!("\"{}\"", s),
+        other => display_value(other),
     }
-    Some(DataHolder::INTEGER32(0)) 
 }
 
-fn println_fn(args: Vec<DataHolder>) -> Option<DataHolder> {
-    let result = print_fn(args);
-    println!(); 
-    result
+fn print_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    let line: Vec<String> = args.iter().map(display_value).collect();
+    runtime.write_output(&line.join(" "))?;
+    Ok(DataHolder::INTEGER32(0))
 }
 
-fn len_fn(args: Vec<DataHolder>) -> Option<DataHolder> {
+fn println_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    let result = print_fn(runtime, args)?;
+    runtime.write_output("\n")?;
+    Ok(result)
+}
+
+fn len_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
     if args.len() != 1 {
-        eprintln!("Error: len() expects exactly 1 argument, got {}", args.len());
-        return None;
+        return Err(RuntimeError::ArityMismatch { name: "len".to_string(), expected: 1, found: args.len() });
     }
     match &args[0] {
-        DataHolder::STRING(s) => Some(DataHolder::INTEGER32(s.len() as i32)),
-        DataHolder::LIST(list) => Some(DataHolder::INTEGER32(list.len() as i32)),
-        _ => {
-            eprintln!("Error: len() can only be called on strings or lists");
-            None
+        DataHolder::STRING(s) => Ok(DataHolder::INTEGER32(s.chars().count() as i32)),
+        DataHolder::LIST(list) => Ok(DataHolder::INTEGER32(list.len() as i32)),
+        DataHolder::MAP(entries) => Ok(DataHolder::INTEGER32(entries.len() as i32)),
+        other => Err(RuntimeError::TypeMismatch(format!("len() expects a string, list or map, got {}", other.get_type()))),
+    }
+}
+
+/// `range(end)`, `range(start, end)` or `range(start, end, step)`: the integers from `start`
+/// up to but not including `end`, as a list. A negative step counts down.
+fn range_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    let (start, end, step) = match args.as_slice() {
+        [end] => (DataHolder::INTEGER32(0), end.clone(), DataHolder::INTEGER32(1)),
+        [start, end] => (start.clone(), end.clone(), DataHolder::INTEGER32(1)),
+        [start, end, step] => (start.clone(), end.clone(), step.clone()),
+        _ => return Err(RuntimeError::ArityMismatch { name: "range".to_string(), expected: 3, found: args.len() }),
+    };
+
+    // The list holds i64 values if any argument is an i64, and i32 values otherwise.
+    let mut wide = false;
+    let mut bounds = [0i64; 3];
+    for (bound, value) in bounds.iter_mut().zip([&start, &end, &step]) {
+        *bound = match value {
+            DataHolder::INTEGER32(n) => *n as i64,
+            DataHolder::INTEGER64(n) => {
+                wide = true;
+                *n
+            },
+            other => return Err(RuntimeError::TypeMismatch(format!("range() expects integer arguments, got {}", other.get_type()))),
+        };
+    }
+
+    let [start, end, step] = bounds;
+    if step == 0 {
+        return Err(RuntimeError::InvalidOperation("range() step must not be zero".to_string()));
+    }
+
+    let mut values = Vec::new();
+    let mut current = start;
+    while (step > 0 && current < end) || (step < 0 && current > end) {
+        values.push(if wide { DataHolder::INTEGER64(current) } else { DataHolder::INTEGER32(current as i32) });
+        match current.checked_add(step) {
+            Some(next) => current = next,
+            None => break,
         }
     }
+    Ok(DataHolder::LIST(values))
 }
 
-fn current_time_fn(_args: Vec<DataHolder>) -> Option<DataHolder> {
+fn current_time_fn(_runtime: &mut Runtime, _args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
     
     use std::time::{SystemTime, UNIX_EPOCH};
     
     match SystemTime::now().duration_since(UNIX_EPOCH) {
         Ok(duration) => {
             let timestamp = duration.as_secs();
-            Some(DataHolder::INTEGER64(timestamp as i64))
+            Ok(DataHolder::INTEGER64(timestamp as i64))
         },
-        Err(_) => {
-            eprintln!("Error: Failed to get current time");
-            None
-        }
+        Err(_) => Err(RuntimeError::InvalidOperation("failed to get current time".to_string())),
     }
 }
 
-fn to_string_fn(args: Vec<DataHolder>) -> Option<DataHolder> {
+/// `set_recursion_limit(n)`: how deeply function calls may nest from now on.
+fn set_recursion_limit_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_arg_count("set_recursion_limit", &args, 1, 1)?;
+    let limit = match &args[0] {
+        DataHolder::INTEGER32(n) => *n as i64,
+        DataHolder::INTEGER64(n) => *n,
+        other => {
+            return Err(RuntimeError::TypeMismatch(format!("set_recursion_limit() expects an integer, got {}", other.get_type())));
+        },
+    };
+    if limit < 1 {
+        return Err(RuntimeError::InvalidOperation(format!("set_recursion_limit() expects a positive limit, got {}", limit)));
+    }
+    if limit > MAX_RECURSION_LIMIT as i64 {
+        return Err(RuntimeError::InvalidOperation(format!(
+            "set_recursion_limit() accepts at most {}, got {}", MAX_RECURSION_LIMIT, limit
+        )));
+    }
+    runtime.set_recursion_limit(limit as usize);
+    Ok(DataHolder::INTEGER32(0))
+}
+
+fn to_string_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
     if args.len() != 1 {
-        eprintln!("Error: to_string() expects exactly 1 argument, got {}", args.len());
-        return None;
+        return Err(RuntimeError::ArityMismatch { name: "to_string".to_string(), expected: 1, found: args.len() });
     }
     
     match &args[0] {
-        DataHolder::INTEGER32(n) => Some(DataHolder::STRING(n.to_string())),
-        DataHolder::INTEGER64(n) => Some(DataHolder::STRING(n.to_string())),
-        DataHolder::FLOAT32(n) => Some(DataHolder::STRING(n.to_string())),
-        DataHolder::FLOAT64(n) => Some(DataHolder::STRING(n.to_string())),
-        DataHolder::BOOLEAN(b) => Some(DataHolder::STRING(b.to_string())),
-        DataHolder::STRING(s) => Some(DataHolder::STRING(s.clone())), 
-        DataHolder::LIST(_) => {
-            eprintln!("Error: Cannot convert list to string directly");
-            None
-        },
-        _ => {
-            eprintln!("Error: Cannot convert this type to string");
-            None
-        }
+        DataHolder::INTEGER32(n) => Ok(DataHolder::STRING(n.to_string())),
+        DataHolder::INTEGER64(n) => Ok(DataHolder::STRING(n.to_string())),
+        DataHolder::FLOAT32(n) => Ok(DataHolder::STRING(display_float(n.to_string()))),
+        DataHolder::FLOAT64(n) => Ok(DataHolder::STRING(display_float(n.to_string()))),
+        DataHolder::BOOLEAN(b) => Ok(DataHolder::STRING(b.to_string())),
+        DataHolder::STRING(s) => Ok(DataHolder::STRING(s.clone())), 
+        other => Err(RuntimeError::TypeMismatch(format!("cannot convert {} to string", other.get_type()))),
     }
 }
 
-fn parse_int_fn(args: Vec<DataHolder>) -> Option<DataHolder> {
+fn parse_int_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
     if args.len() != 1 {
-        eprintln!("Error: parse_int() expects exactly 1 argument, got {}", args.len());
-        return None;
+        return Err(RuntimeError::ArityMismatch { name: "parse_int".to_string(), expected: 1, found: args.len() });
     }
     
     match &args[0] {
         DataHolder::STRING(s) => {
             match s.parse::<i32>() {
-                Ok(n) => Some(DataHolder::INTEGER32(n)),
-                Err(_) => {
-                    eprintln!("Error: Cannot parse '{}' as integer", s);
-                    None
-                }
+                Ok(n) => Ok(DataHolder::INTEGER32(n)),
+                Err(_) => Err(RuntimeError::InvalidOperation(format!("cannot parse '{}' as integer", s))),
             }
         },
-        DataHolder::INTEGER32(n) => Some(DataHolder::INTEGER32(*n)), 
-        DataHolder::INTEGER64(n) => Some(DataHolder::INTEGER32(*n as i32)), 
-        DataHolder::FLOAT32(n) => Some(DataHolder::INTEGER32(*n as i32)), 
-        DataHolder::FLOAT64(n) => Some(DataHolder::INTEGER32(*n as i32)), 
-        _ => {
-            eprintln!("Error: Cannot parse this type as integer");
-            None
+        DataHolder::INTEGER32(n) => Ok(DataHolder::INTEGER32(*n)), 
+        DataHolder::INTEGER64(n) => Ok(DataHolder::INTEGER32(*n as i32)), 
+        DataHolder::FLOAT32(n) => Ok(DataHolder::INTEGER32(*n as i32)), 
+        DataHolder::FLOAT64(n) => Ok(DataHolder::INTEGER32(*n as i32)), 
+        other => Err(RuntimeError::TypeMismatch(format!("cannot parse {} as integer", other.get_type()))),
+    }
+}
+
+fn to_i32_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    cast("to_i32", Types::INTEGER32, args)
+}
+
+fn to_i64_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    cast("to_i64", Types::INTEGER64, args)
+}
+
+fn to_f32_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    cast("to_f32", Types::FLOAT32, args)
+}
+
+fn to_f64_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    cast("to_f64", Types::FLOAT64, args)
+}
+
+/// Converts a number, or a string holding one, to `target`. Floats convert to integers by
+/// dropping the fraction. A value that doesn't fit in `target` is an error rather than being
+/// wrapped or saturated.
+fn cast(name: &str, target: Types, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    if args.len() != 1 {
+        return Err(RuntimeError::ArityMismatch { name: name.to_string(), expected: 1, found: args.len() });
+    }
+
+    let converted = match &args[0] {
+        DataHolder::INTEGER32(n) => int_to(*n as i64, &target),
+        DataHolder::INTEGER64(n) => int_to(*n, &target),
+        DataHolder::FLOAT32(n) => float_to(*n as f64, &target),
+        DataHolder::FLOAT64(n) => float_to(*n, &target),
+        DataHolder::STRING(s) => {
+            let text = s.trim();
+            let parsed = match target {
+                Types::INTEGER32 | Types::INTEGER64 => text.parse::<i64>().ok().map(|n| int_to(n, &target)),
+                _ => text.parse::<f64>().ok().map(|n| float_to(n, &target)),
+            };
+            match parsed {
+                Some(converted) => converted,
+                None => return Err(RuntimeError::InvalidOperation(format!("cannot parse '{}' as {}", s, target))),
+            }
+        },
+        other => return Err(RuntimeError::TypeMismatch(format!("{}() expects a number or string, got {}", name, other.get_type()))),
+    };
+
+    converted.ok_or_else(|| RuntimeError::InvalidOperation(format!("{} is out of range for {}", display_value(&args[0]), target)))
+}
+
+fn int_to(n: i64, target: &Types) -> Option<DataHolder> {
+    match target {
+        Types::INTEGER32 => i32::try_from(n).ok().map(DataHolder::INTEGER32),
+        Types::INTEGER64 => Some(DataHolder::INTEGER64(n)),
+        Types::FLOAT32 => Some(DataHolder::FLOAT32(n as f32)),
+        _ => Some(DataHolder::FLOAT64(n as f64)),
+    }
+}
+
+fn float_to(n: f64, target: &Types) -> Option<DataHolder> {
+    let whole = n.trunc();
+    match target {
+        Types::INTEGER32 if whole >= i32::MIN as f64 && whole <= i32::MAX as f64 => Some(DataHolder::INTEGER32(whole as i32)),
+        // 2^63 is the first whole f64 too big for an i64; every smaller one fits.
+        Types::INTEGER64 if whole >= i64::MIN as f64 && whole < i64::MAX as f64 => Some(DataHolder::INTEGER64(whole as i64)),
+        Types::INTEGER32 | Types::INTEGER64 => None,
+        Types::FLOAT32 => Some(DataHolder::FLOAT32(n as f32)),
+        _ => Some(DataHolder::FLOAT64(n)),
+    }
+}
+
+/// Checks that between `min` and `max` arguments were passed.
+fn expect_arg_count(name: &str, args: &[DataHolder], min: usize, max: usize) -> Result<(), RuntimeError> {
+    if args.len() < min || args.len() > max {
+        let expected = if args.len() < min { min } else { max };
+        return Err(RuntimeError::ArityMismatch { name: name.to_string(), expected, found: args.len() });
+    }
+    Ok(())
+}
+
+/// The argument at `position` when it is a function value.
+fn function_arg(name: &str, args: &[DataHolder], position: usize) -> Result<DataHolder, RuntimeError> {
+    match &args[position] {
+        function @ DataHolder::FUNCTION(_) => Ok(function.clone()),
+        other => Err(RuntimeError::TypeMismatch(format!(
+            "{}() expects a function as argument {}, got {}", name, position + 1, other.get_type()
+        ))),
+    }
+}
+
+/// Calls a predicate, which must answer with a bool.
+fn call_predicate(runtime: &mut Runtime, name: &str, predicate: &DataHolder, item: DataHolder) -> Result<bool, RuntimeError> {
+    match runtime.call_value(predicate, vec![item])? {
+        DataHolder::BOOLEAN(answer) => Ok(answer),
+        other => Err(RuntimeError::TypeMismatch(format!("{}() callback must return bool, got {}", name, other.get_type()))),
+    }
+}
+
+/// `map(items, f)`: a list of `f(item)` for each item.
+fn map_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_arg_count("map", &args, 2, 2)?;
+    let function = function_arg("map", &args, 1)?;
+    let mut results = Vec::new();
+    for item in Runtime::iterable_items(args[0].clone())? {
+        results.push(runtime.call_value(&function, vec![item])?);
+    }
+    Ok(DataHolder::LIST(results))
+}
+
+/// `filter(items, predicate)`: the items for which the predicate returns `true`.
+fn filter_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_arg_count("filter", &args, 2, 2)?;
+    let predicate = function_arg("filter", &args, 1)?;
+    let mut kept = Vec::new();
+    for item in Runtime::iterable_items(args[0].clone())? {
+        if call_predicate(runtime, "filter", &predicate, item.clone())? {
+            kept.push(item);
+        }
+    }
+    Ok(DataHolder::LIST(kept))
+}
+
+/// `reduce(items, f)` or `reduce(items, f, initial)`: folds the items from the left with
+/// `f(accumulated, item)`. Without `initial`, the first item starts the fold.
+fn reduce_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_arg_count("reduce", &args, 2, 3)?;
+    let function = function_arg("reduce", &args, 1)?;
+    let mut items = Runtime::iterable_items(args[0].clone())?.into_iter();
+    let mut accumulated = match args.get(2) {
+        Some(initial) => initial.clone(),
+        None => items.next().ok_or_else(|| {
+            RuntimeError::InvalidOperation("reduce() of an empty list with no initial value".to_string())
+        })?,
+    };
+    for item in items {
+        accumulated = runtime.call_value(&function, vec![accumulated, item])?;
+    }
+    Ok(accumulated)
+}
+
+/// `sort_by(items, key)`: a new list of the items ordered by `key(item)`. Items with equal
+/// keys keep their order. The keys must all be the same kind of number, or all strings.
+fn sort_by_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_arg_count("sort_by", &args, 2, 2)?;
+    let key = function_arg("sort_by", &args, 1)?;
+    let items = Runtime::iterable_items(args[0].clone())?;
+    let mut keys = Vec::new();
+    for item in &items {
+        keys.push(runtime.call_value(&key, vec![item.clone()])?);
+    }
+    if let Some((key_type, other)) = unsortable_pair(&keys) {
+        return Err(RuntimeError::TypeMismatch(format!("cannot sort by keys of type {} and {}", key_type, other)));
+    }
+
+    let mut keyed: Vec<(DataHolder, DataHolder)> = keys.into_iter().zip(items).collect();
+    keyed.sort_by(|(left, _), (right, _)| compare_values(left, right));
+    Ok(DataHolder::LIST(keyed.into_iter().map(|(_, item)| item).collect()))
+}
+
+/// Shared by `any` and `all`: whether `predicate` (or, without one, the item itself) gives
+/// `looking_for` for some item. Stops at the first one that does.
+fn find_answer(runtime: &mut Runtime, name: &str, args: Vec<DataHolder>, looking_for: bool) -> Result<bool, RuntimeError> {
+    expect_arg_count(name, &args, 1, 2)?;
+    let predicate = match args.len() {
+        2 => Some(function_arg(name, &args, 1)?),
+        _ => None,
+    };
+    for item in Runtime::iterable_items(args[0].clone())? {
+        let answer = match (&predicate, item) {
+            (Some(predicate), item) => call_predicate(runtime, name, predicate, item)?,
+            (None, DataHolder::BOOLEAN(answer)) => answer,
+            (None, other) => {
+                return Err(RuntimeError::TypeMismatch(format!("{}() expects bool items, got {}", name, other.get_type())));
+            },
+        };
+        if answer == looking_for {
+            return Ok(true);
         }
     }
+    Ok(false)
+}
+
+/// `any(items)` or `any(items, predicate)`: whether any item is, or satisfies the predicate,
+/// `true`. `false` for no items.
+fn any_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    Ok(DataHolder::BOOLEAN(find_answer(runtime, "any", args, true)?))
+}
+
+/// `all(items)` or `all(items, predicate)`: whether every item is, or satisfies the
+/// predicate, `true`. `true` for no items.
+fn all_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    Ok(DataHolder::BOOLEAN(!find_answer(runtime, "all", args, false)?))
+}
+
+/// `zip(a, b)`: a list of `[a[i], b[i]]` pairs, as long as the shorter input.
+fn zip_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_arg_count("zip", &args, 2, 2)?;
+    let left = Runtime::iterable_items(args[0].clone())?;
+    let right = Runtime::iterable_items(args[1].clone())?;
+    let pairs = left.into_iter().zip(right).map(|(a, b)| DataHolder::LIST(vec![a, b])).collect();
+    Ok(DataHolder::LIST(pairs))
+}
+
+/// `enumerate(items)`: a list of `[index, item]` pairs, counting from 0.
+fn enumerate_fn(_runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_arg_count("enumerate", &args, 1, 1)?;
+    let pairs = Runtime::iterable_items(args[0].clone())?.into_iter()
+        .enumerate()
+        .map(|(index, item)| DataHolder::LIST(vec![DataHolder::INTEGER32(index as i32), item]))
+        .collect();
+    Ok(DataHolder::LIST(pairs))
+}
+
+/// `sum(items)`: the items added with `+`, so mixed numbers promote and overflow is an error.
+/// 0 for no items.
+fn sum_fn(runtime: &mut Runtime, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_arg_count("sum", &args, 1, 1)?;
+    let mut total = DataHolder::INTEGER32(0);
+    for item in Runtime::iterable_items(args[0].clone())? {
+        total = runtime.perform_arithmetic_operation(&total, &ArithmeticOperator::Add, &item)?;
+    }
+    Ok(total)
 }
@@ -0,0 +1,406 @@
+use std::{collections::HashMap, sync::OnceLock};
+use std::cmp::Ordering;
+use crate::tokenizer::Types;
+use crate::values::DataHolder;
+use crate::runtime::{map_lookup, values_equal, Runtime, RuntimeError};
+use crate::Functions::display_value;
+
+type NativeMethodFn = fn(&mut DataHolder, Vec<DataHolder>) -> Result<DataHolder, RuntimeError>;
+
+/// A method implemented in Rust on a built-in value type such as `list`.
+pub struct NativeMethod {
+    pub function: NativeMethodFn,
+    /// Whether the method changes its receiver. The runtime then stores the changed value
+    /// back into the variable, field or element the receiver was read from.
+    pub mutates: bool,
+    /// What the method returns, for the type checker. `None` when it depends on the contents.
+    pub return_type: Option<Types>,
+}
+
+/// Methods on built-in types, keyed by the type's name as `Types` displays it. To give another
+/// type methods, write functions with the `NativeMethodFn` signature and register them in `new`.
+pub struct NativeMethods {
+    methods: HashMap<&'static str, HashMap<&'static str, NativeMethod>>,
+}
+
+impl NativeMethods {
+    pub fn new() -> Self {
+        let mut methods = NativeMethods { methods: HashMap::new() };
+
+        methods.register("list", "push", list_push, true, Some(Types::INTEGER32));
+        methods.register("list", "pop", list_pop, true, None);
+        methods.register("list", "insert", list_insert, true, Some(Types::INTEGER32));
+        methods.register("list", "remove", list_remove, true, Some(Types::INTEGER32));
+        methods.register("list", "extend", list_extend, true, Some(Types::INTEGER32));
+        methods.register("list", "sort", list_sort, true, Some(Types::INTEGER32));
+        methods.register("list", "reverse", list_reverse, true, Some(Types::INTEGER32));
+        methods.register("list", "contains", list_contains, false, Some(Types::BOOLEAN));
+        methods.register("list", "index_of", list_index_of, false, Some(Types::INTEGER32));
+        methods.register("list", "join", list_join, false, Some(Types::STRING));
+
+        methods.register("map", "keys", map_keys, false, Some(Types::LIST));
+        methods.register("map", "values", map_values, false, Some(Types::LIST));
+        methods.register("map", "items", map_items, false, Some(Types::LIST));
+        methods.register("map", "has", map_has, false, Some(Types::BOOLEAN));
+
+        methods.register("string", "split", string_split, false, Some(Types::LIST));
+        methods.register("string", "trim", string_trim, false, Some(Types::STRING));
+        methods.register("string", "upper", string_upper, false, Some(Types::STRING));
+        methods.register("string", "lower", string_lower, false, Some(Types::STRING));
+        methods.register("string", "replace", string_replace, false, Some(Types::STRING));
+        methods.register("string", "starts_with", string_starts_with, false, Some(Types::BOOLEAN));
+        methods.register("string", "ends_with", string_ends_with, false, Some(Types::BOOLEAN));
+        methods.register("string", "find", string_find, false, Some(Types::INTEGER32));
+        methods.register("string", "chars", string_chars, false, Some(Types::LIST));
+        methods.register("string", "repeat", string_repeat, false, Some(Types::STRING));
+        methods.register("string", "substring", string_substring, false, Some(Types::STRING));
+        methods.register("string", "format", string_format, false, Some(Types::STRING));
+
+        methods
+    }
+
+    fn register(&mut self, type_name: &'static str, name: &'static str, function: NativeMethodFn, mutates: bool, return_type: Option<Types>) {
+        self.methods.entry(type_name).or_default().insert(name, NativeMethod { function, mutates, return_type });
+    }
+
+    pub fn get(&self, data_type: &Types, name: &str) -> Option<&NativeMethod> {
+        self.methods.get(data_type.to_string().as_str())?.get(name)
+    }
+}
+
+static NATIVE_METHODS: OnceLock<NativeMethods> = OnceLock::new();
+
+pub fn get_native_methods() -> &'static NativeMethods {
+    NATIVE_METHODS.get_or_init(NativeMethods::new)
+}
+
+fn expect_args(name: &str, args: &[DataHolder], expected: usize) -> Result<(), RuntimeError> {
+    if args.len() != expected {
+        return Err(RuntimeError::ArityMismatch { name: name.to_string(), expected, found: args.len() });
+    }
+    Ok(())
+}
+
+/// The argument at `position` as a string, or an error naming the method and argument.
+fn string_arg<'a>(method: &str, args: &'a [DataHolder], position: usize) -> Result<&'a str, RuntimeError> {
+    match &args[position] {
+        DataHolder::STRING(s) => Ok(s),
+        other => Err(RuntimeError::wrong_type(
+            &format!("argument {} of '{}'", position + 1, method), &Types::STRING, &other.get_type(),
+        )),
+    }
+}
+
+fn as_list(receiver: &mut DataHolder) -> &mut Vec<DataHolder> {
+    match receiver {
+        DataHolder::LIST(list) => list,
+        other => unreachable!("list method called on {}", other.get_type()),
+    }
+}
+
+fn list_push(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.push", &args, 1)?;
+    as_list(receiver).extend(args);
+    Ok(DataHolder::INTEGER32(0))
+}
+
+/// `pop()` removes the last element, `pop(i)` the element at `i`; both return it.
+fn list_pop(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    if args.len() > 1 {
+        return Err(RuntimeError::ArityMismatch { name: "list.pop".to_string(), expected: 1, found: args.len() });
+    }
+    let list = as_list(receiver);
+    if list.is_empty() {
+        return Err(RuntimeError::InvalidOperation("pop from an empty list".to_string()));
+    }
+
+    let index = match args.first() {
+        Some(index) => Runtime::index_value(index)?,
+        None => -1,
+    };
+    let position = Runtime::resolve_index(index, list.len())?;
+    Ok(list.remove(position))
+}
+
+/// `insert(i, value)` puts `value` before position `i`. As in Python, `i` may be negative and
+/// is clamped to the list, so inserting past the end appends.
+fn list_insert(receiver: &mut DataHolder, mut args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.insert", &args, 2)?;
+    let value = args.pop().unwrap();
+    let index = Runtime::index_value(&args[0])?;
+
+    let list = as_list(receiver);
+    let length = list.len() as i64;
+    let position = if index < 0 { index + length } else { index };
+    list.insert(position.clamp(0, length) as usize, value);
+    Ok(DataHolder::INTEGER32(0))
+}
+
+/// Removes the first element equal to the argument.
+fn list_remove(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.remove", &args, 1)?;
+    let list = as_list(receiver);
+    match list.iter().position(|item| values_equal(item, &args[0])) {
+        Some(position) => {
+            list.remove(position);
+            Ok(DataHolder::INTEGER32(0))
+        },
+        None => Err(RuntimeError::InvalidOperation(format!("{} is not in the list", display_value(&args[0])))),
+    }
+}
+
+fn list_extend(receiver: &mut DataHolder, mut args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.extend", &args, 1)?;
+    match args.pop().unwrap() {
+        DataHolder::LIST(items) => {
+            as_list(receiver).extend(items);
+            Ok(DataHolder::INTEGER32(0))
+        },
+        other => Err(RuntimeError::TypeMismatch(format!("list.extend() expects a list, got {}", other.get_type()))),
+    }
+}
+
+/// Sorts in ascending order. Every element must be a number of one type, or every element a
+/// string.
+fn list_sort(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.sort", &args, 0)?;
+    let list = as_list(receiver);
+
+    if let Some((element_type, other)) = unsortable_pair(list) {
+        return Err(RuntimeError::TypeMismatch(format!(
+            "cannot sort a list containing {} and {}", element_type, other
+        )));
+    }
+
+    list.sort_by(compare_values);
+    Ok(DataHolder::INTEGER32(0))
+}
+
+/// Values can be sorted when all are the same kind of number, or all are strings. Otherwise
+/// returns the type of the first value and of the first one that can't be ordered against it.
+pub fn unsortable_pair(values: &[DataHolder]) -> Option<(Types, Types)> {
+    let element_type = values.first()?.get_type();
+    let sortable = matches!(element_type, Types::INTEGER32 | Types::INTEGER64 | Types::FLOAT32 | Types::FLOAT64 | Types::STRING);
+    values.iter()
+        .find(|item| !sortable || item.get_type() != element_type)
+        .map(|other| (element_type, other.get_type()))
+}
+
+/// Orders two values already known to be of the same sortable type. NaN sorts as equal.
+pub fn compare_values(left: &DataHolder, right: &DataHolder) -> Ordering {
+    match (left, right) {
+        (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => a.cmp(b),
+        (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => a.cmp(b),
+        (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (DataHolder::STRING(a), DataHolder::STRING(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+fn list_reverse(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.reverse", &args, 0)?;
+    as_list(receiver).reverse();
+    Ok(DataHolder::INTEGER32(0))
+}
+
+fn list_contains(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.contains", &args, 1)?;
+    Ok(DataHolder::BOOLEAN(as_list(receiver).iter().any(|item| values_equal(item, &args[0]))))
+}
+
+/// The position of the first element equal to the argument, or -1 if there is none.
+fn list_index_of(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.index_of", &args, 1)?;
+    let position = as_list(receiver).iter().position(|item| values_equal(item, &args[0]));
+    Ok(DataHolder::INTEGER32(position.map_or(-1, |position| position as i32)))
+}
+
+/// Joins the elements, formatted as `print` would show them, with the separator in between.
+fn list_join(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("list.join", &args, 1)?;
+    let separator = string_arg("list.join", &args, 0)?;
+    let parts: Vec<String> = as_list(receiver).iter().map(display_value).collect();
+    Ok(DataHolder::STRING(parts.join(separator)))
+}
+
+fn as_map(receiver: &DataHolder) -> &[(DataHolder, DataHolder)] {
+    match receiver {
+        DataHolder::MAP(entries) => entries,
+        other => unreachable!("map method called on {}", other.get_type()),
+    }
+}
+
+fn map_keys(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("map.keys", &args, 0)?;
+    Ok(DataHolder::LIST(as_map(receiver).iter().map(|(key, _)| key.clone()).collect()))
+}
+
+fn map_values(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("map.values", &args, 0)?;
+    Ok(DataHolder::LIST(as_map(receiver).iter().map(|(_, value)| value.clone()).collect()))
+}
+
+/// The entries as a list of `[key, value]` pairs, in insertion order.
+fn map_items(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("map.items", &args, 0)?;
+    let items = as_map(receiver).iter().map(|(key, value)| DataHolder::LIST(vec![key.clone(), value.clone()]));
+    Ok(DataHolder::LIST(items.collect()))
+}
+
+fn map_has(receiver: &mut DataHolder, mut args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("map.has", &args, 1)?;
+    let key = Runtime::map_key(args.pop().unwrap())?;
+    Ok(DataHolder::BOOLEAN(map_lookup(as_map(receiver), &key).is_some()))
+}
+
+fn as_string(receiver: &DataHolder) -> &str {
+    match receiver {
+        DataHolder::STRING(s) => s,
+        other => unreachable!("string method called on {}", other.get_type()),
+    }
+}
+
+fn strings_to_list<'a>(parts: impl Iterator<Item = &'a str>) -> DataHolder {
+    DataHolder::LIST(parts.map(|part| DataHolder::STRING(part.to_string())).collect())
+}
+
+/// `split(sep)` splits on every `sep`; `split()` splits on runs of whitespace and drops the
+/// empty pieces at either end.
+fn string_split(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    let text = as_string(receiver);
+    match args.len() {
+        0 => Ok(strings_to_list(text.split_whitespace())),
+        1 => {
+            let separator = string_arg("string.split", &args, 0)?;
+            if separator.is_empty() {
+                return Err(RuntimeError::InvalidOperation("string.split() separator must not be empty".to_string()));
+            }
+            Ok(strings_to_list(text.split(separator)))
+        },
+        found => Err(RuntimeError::ArityMismatch { name: "string.split".to_string(), expected: 1, found }),
+    }
+}
+
+fn string_trim(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.trim", &args, 0)?;
+    Ok(DataHolder::STRING(as_string(receiver).trim().to_string()))
+}
+
+fn string_upper(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.upper", &args, 0)?;
+    Ok(DataHolder::STRING(as_string(receiver).to_uppercase()))
+}
+
+fn string_lower(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.lower", &args, 0)?;
+    Ok(DataHolder::STRING(as_string(receiver).to_lowercase()))
+}
+
+/// Replaces every occurrence of the first argument with the second.
+fn string_replace(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.replace", &args, 2)?;
+    let from = string_arg("string.replace", &args, 0)?;
+    let to = string_arg("string.replace", &args, 1)?;
+    Ok(DataHolder::STRING(as_string(receiver).replace(from, to)))
+}
+
+fn string_starts_with(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.starts_with", &args, 1)?;
+    let prefix = string_arg("string.starts_with", &args, 0)?;
+    Ok(DataHolder::BOOLEAN(as_string(receiver).starts_with(prefix)))
+}
+
+fn string_ends_with(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.ends_with", &args, 1)?;
+    let suffix = string_arg("string.ends_with", &args, 0)?;
+    Ok(DataHolder::BOOLEAN(as_string(receiver).ends_with(suffix)))
+}
+
+/// The character position of the first occurrence of the argument, or -1 if there is none.
+fn string_find(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.find", &args, 1)?;
+    let needle = string_arg("string.find", &args, 0)?;
+    let text = as_string(receiver);
+    let position = text.find(needle).map_or(-1, |byte| text[..byte].chars().count() as i32);
+    Ok(DataHolder::INTEGER32(position))
+}
+
+/// The characters of the string, each as a one-character string.
+fn string_chars(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.chars", &args, 0)?;
+    let chars = as_string(receiver).chars().map(|ch| DataHolder::STRING(ch.to_string()));
+    Ok(DataHolder::LIST(chars.collect()))
+}
+
+/// The longest string, in bytes, that `repeat` builds. Longer results would exhaust memory
+/// and abort the interpreter instead of raising an error.
+const MAX_REPEAT_LENGTH: usize = 1 << 30;
+
+fn string_repeat(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    expect_args("string.repeat", &args, 1)?;
+    let count = Runtime::index_value(&args[0])?;
+    if count < 0 {
+        return Err(RuntimeError::InvalidOperation(format!("string.repeat() count must not be negative, got {}", count)));
+    }
+    let text = as_string(receiver);
+    match text.len().checked_mul(count as usize) {
+        Some(length) if length <= MAX_REPEAT_LENGTH => Ok(DataHolder::STRING(text.repeat(count as usize))),
+        _ => Err(RuntimeError::InvalidOperation(format!(
+            "string.repeat() result would be too long: {} copies of {} bytes, more than {} bytes in all",
+            count, text.len(), MAX_REPEAT_LENGTH
+        ))),
+    }
+}
+
+/// `substring(start)` or `substring(start, end)`, counted in characters. The bounds work like
+/// a slice's, so `s.substring(a, b)` is `s[a:b]`.
+fn string_substring(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(RuntimeError::ArityMismatch { name: "string.substring".to_string(), expected: 2, found: args.len() });
+    }
+    let start = Runtime::index_value(&args[0])?;
+    let end = args.get(1).map(Runtime::index_value).transpose()?;
+
+    let chars: Vec<char> = as_string(receiver).chars().collect();
+    let (from, to) = Runtime::resolve_slice(Some(start), end, chars.len());
+    Ok(DataHolder::STRING(chars[from..to].iter().collect()))
+}
+
+/// Replaces each `{}` with the next argument, formatted as `print` would show it. `{{` and
+/// `}}` stand for literal braces.
+fn string_format(receiver: &mut DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+    let template = as_string(receiver);
+    let mut result = String::new();
+    let mut values = args.iter();
+    let mut used = 0;
+
+    let mut chars = template.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(ch);
+            },
+            ('{', Some('}')) => {
+                chars.next();
+                let value = values.next().ok_or_else(|| RuntimeError::InvalidOperation(format!(
+                    "format string has more placeholders than the {} arguments given", args.len()
+                )))?;
+                result.push_str(&display_value(value));
+                used += 1;
+            },
+            ('{', _) | ('}', _) => {
+                return Err(RuntimeError::InvalidOperation(format!("unmatched '{}' in format string", ch)));
+            },
+            _ => result.push(ch),
+        }
+    }
+
+    if used < args.len() {
+        return Err(RuntimeError::InvalidOperation(format!(
+            "format string has {} placeholders but {} arguments were given", used, args.len()
+        )));
+    }
+    Ok(DataHolder::STRING(result))
+}
@@ -1,14 +1,16 @@
-use crate::Tokenizer;
-use crate::ASTParser;
-use crate::Runtime;
-use std;
-use std::io::Read;
-use std::io::{self, Write};
+use crate::tokenizer::Tokenizer;
+use crate::tokenizer::Tokens;
+use crate::AstTree::ASTParser;
+use crate::runtime::Runtime;
+use crate::typecheck::TypeChecker;
+use crate::diagnostics;
+use std::io::Write;
 
 pub fn start_repl() {
     let tokenizer = Tokenizer::new();
     let mut parser = ASTParser::new();
     let mut runtime = Runtime::new();
+    let mut checker = TypeChecker::new();
     
     let stdin = std::io::stdin();
     loop {
@@ -22,9 +24,35 @@ pub fn start_repl() {
             break;
         }
         
-        let tokens = tokenizer.process_content(&input);
-        let statements = parser.parse(tokens);
-        runtime.execute_statements(statements);
+        let tokens = match tokenizer.process_content(&input) {
+            Ok(tokens) => tokens,
+            Err(error) => {
+                eprintln!("{}", diagnostics::render(&input, "<repl>", error.span, &error.message));
+                continue;
+            }
+        };
+        let statements = match parser.parse(tokens) {
+            Ok(statements) => statements,
+            Err(errors) => {
+                for error in &errors {
+                    eprintln!("{}", diagnostics::render(&input, "<repl>", error.span, &error.message));
+                }
+                continue;
+            }
+        };
+        if let Err(errors) = checker.check(&statements) {
+            for error in &errors {
+                eprintln!("{}", diagnostics::render(&input, "<repl>", error.span, &error.message));
+            }
+            continue;
+        }
+        if let Err(error) = runtime.execute_statements(statements) {
+            let traceback = runtime.take_traceback();
+            if !traceback.is_empty() {
+                eprint!("{}", diagnostics::render_traceback(&input, "<repl>", &traceback, &error));
+            }
+            eprintln!("{}", diagnostics::render_runtime_error(&input, "<repl>", &error));
+        }
     }
 }
 
@@ -32,7 +60,6 @@ fn read_multi_line_input(stdin: &std::io::Stdin) -> String {
     let mut input = String::new();
     let mut line_buffer = String::new();
     let mut brace_count = 0;
-    let mut consecutive_empty_lines = 0;
     
     print!(">> ");
     std::io::stdout().flush().unwrap();
@@ -56,22 +83,18 @@ fn read_multi_line_input(stdin: &std::io::Stdin) -> String {
         input.push_str(line);
         input.push('\n');
 
+        // A blank line ends the input unless a block is still open.
         if trimmed_line.is_empty() {
-            consecutive_empty_lines += 1;
-            if brace_count == 0 && consecutive_empty_lines >= 2 {
+            if brace_count <= 0 {
                 break;
             }
             print!(".. ");
             std::io::stdout().flush().unwrap();
             continue;
-        } else {
-            consecutive_empty_lines = 0;
         }
 
         if brace_count == 0 {
-            let full_input = input.trim();
-
-            if full_input.starts_with("if") && !full_input.contains("else") {
+            if awaits_else(&input) {
                 print!(".. ");
                 std::io::stdout().flush().unwrap();
                 continue;
@@ -89,3 +112,37 @@ fn read_multi_line_input(stdin: &std::io::Stdin) -> String {
     input
 }
 
+/// Whether `input` ends with the block of an `if` or `else if`, so the next line may still
+/// continue the chain with `else`. A chain already closed by a plain `else { }` is complete.
+fn awaits_else(input: &str) -> bool {
+    let Ok(tokens) = Tokenizer::new().process_content(input) else {
+        return false;
+    };
+    let Some(last) = tokens.last() else {
+        return false;
+    };
+    if last.kind != Tokens::RBRACE {
+        return false;
+    }
+
+    // Walk back to the `{` that opens the final block, then over its header up to the end of
+    // the previous top-level block.
+    let mut depth = 0;
+    let mut index = tokens.len();
+    while index > 0 {
+        index -= 1;
+        match tokens[index].kind {
+            Tokens::RBRACE => depth += 1,
+            Tokens::LBRACE => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            break;
+        }
+    }
+
+    tokens[..index].iter()
+        .rev()
+        .take_while(|token| token.kind != Tokens::RBRACE)
+        .any(|token| token.kind == Tokens::IF)
+}
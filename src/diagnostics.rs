@@ -0,0 +1,120 @@
+use crate::tokenizer::Span;
+use crate::runtime::{Frame, RuntimeError};
+
+/// After this many copies in a row of the same traceback entry, or the same cycle of entries,
+/// the rest are summarised in one line.
+const REPEATED_ENTRIES_SHOWN: usize = 3;
+
+/// The longest cycle of calls, such as `f` calling `g` calling `f` again, that is recognised
+/// as repeating.
+const LONGEST_CYCLE: usize = 8;
+
+/// A traceback still longer than this once cycles are collapsed shows only its first and last
+/// `ENTRIES_SHOWN / 2` entries.
+const ENTRIES_SHOWN: usize = 40;
+
+/// Formats `message` in the style of rustc: a header, the `file:line:column`
+/// location, the offending source line and carets under the spanned text.
+pub fn render(source: &str, file_name: &str, span: Span, message: &str) -> String {
+    let line_text = source.lines().nth(span.line.saturating_sub(1)).unwrap_or("");
+    let line_number = span.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+
+    let start_column = span.column.saturating_sub(1);
+    let available = line_text.chars().count().saturating_sub(start_column);
+    let spanned = source.get(span.start..span.end)
+        .map(|text| text.chars().take_while(|ch| *ch != '\n').count())
+        .unwrap_or(0);
+    let width = spanned.min(available).max(1);
+
+    let padding: String = line_text.chars()
+        .take(start_column)
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    format!(
+        "error: {}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{}",
+        message,
+        gutter, file_name, span.line, span.column,
+        gutter,
+        line_number, line_text,
+        gutter, padding, "^".repeat(width),
+    )
+}
+
+/// A Python-style traceback for an error raised inside the calls in `frames`, outermost first:
+/// one entry per call site, then one for where the error was raised, each with its source
+/// line. Printed above the error itself. Repeating entries, or cycles of them, as from a
+/// function calling itself directly or through others, are cut short, and a traceback that is
+/// still long is shown only at its two ends.
+pub fn render_traceback(source: &str, file_name: &str, frames: &[Frame], error: &RuntimeError) -> String {
+    let callers = std::iter::once("<module>").chain(frames.iter().map(|frame| frame.function.as_str()));
+    let locations = frames.iter().map(|frame| Some(frame.call_site)).chain(std::iter::once(error.span()));
+    let entries: Vec<(&str, Option<Span>)> = callers.zip(locations).collect();
+
+    let render_entry = |(function, location): (&str, Option<Span>)| match location {
+        Some(span) => {
+            let line_text = source.lines().nth(span.line.saturating_sub(1)).unwrap_or("").trim();
+            format!("  {}:{}:{}, in {}\n    {}\n", file_name, span.line, span.column, function, line_text)
+        },
+        None => format!("  in {}\n", function),
+    };
+
+    // Each line of output, with the number of entries it stands for.
+    let mut lines: Vec<(String, usize)> = Vec::new();
+    let mut position = 0;
+    while position < entries.len() {
+        match repeating_cycle(&entries[position..]) {
+            Some((length, copies)) => {
+                let shown = &entries[position..position + length * REPEATED_ENTRIES_SHOWN];
+                lines.extend(shown.iter().map(|entry| (render_entry(*entry), 1)));
+                let repeated = match length {
+                    1 => "entry".to_string(),
+                    _ => format!("{} entries", length),
+                };
+                let hidden = copies - REPEATED_ENTRIES_SHOWN;
+                lines.push((format!("  [previous {} repeated {} more times]\n", repeated, hidden), length * hidden));
+                position += length * copies;
+            },
+            None => {
+                lines.push((render_entry(entries[position]), 1));
+                position += 1;
+            },
+        }
+    }
+
+    if lines.len() > ENTRIES_SHOWN {
+        let middle = ENTRIES_SHOWN / 2..lines.len() - ENTRIES_SHOWN / 2;
+        let omitted: usize = lines[middle.clone()].iter().map(|(_, count)| count).sum();
+        lines.splice(middle, [(format!("  [{} more entries]\n", omitted), omitted)]);
+    }
+    let mut rendered = String::from("traceback (most recent call last):\n");
+    for (line, _) in lines {
+        rendered += &line;
+    }
+    rendered
+}
+
+/// The shortest cycle, `(length, copies)`, that `entries` starts with more than
+/// `REPEATED_ENTRIES_SHOWN` copies of, back to back.
+fn repeating_cycle<T: PartialEq>(entries: &[T]) -> Option<(usize, usize)> {
+    for length in 1..=LONGEST_CYCLE {
+        if entries.len() < length * (REPEATED_ENTRIES_SHOWN + 1) {
+            break;
+        }
+        let cycle = &entries[..length];
+        let copies = entries.chunks_exact(length).take_while(|chunk| *chunk == cycle).count();
+        if copies > REPEATED_ENTRIES_SHOWN {
+            return Some((length, copies));
+        }
+    }
+    None
+}
+
+/// Renders a runtime error under the source it points at, or on its own if it has no span.
+pub fn render_runtime_error(source: &str, file_name: &str, error: &RuntimeError) -> String {
+    match error.span() {
+        Some(span) => render(source, file_name, span, &error.to_string()),
+        None => format!("error: {}", error),
+    }
+}
@@ -0,0 +1,112 @@
+//! Golden-output tests. Every `name.oxy` in a directory is run with its output captured, and
+//! what it printed is compared with `name.stdout` and `name.stderr` beside it. A missing
+//! expectation file means that stream should stay empty.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use crate::interpreter::{run_source, with_large_stack};
+
+/// A captured stream that stays readable after the runtime holding it is dropped.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A stream whose output differs from its expectation file.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    /// `"stdout"` or `"stderr"`.
+    pub stream: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut expected = self.expected.lines();
+        let mut actual = self.actual.lines();
+        let mut line = 1;
+        loop {
+            match (expected.next(), actual.next()) {
+                (Some(want), Some(got)) if want == got => line += 1,
+                (want, got) => {
+                    return write!(
+                        f,
+                        "{} differs at line {}:\n  expected: {}\n  actual:   {}",
+                        self.stream, line,
+                        want.unwrap_or("<end of output>"),
+                        got.unwrap_or("<end of output>"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GoldenResult {
+    pub script: PathBuf,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl GoldenResult {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Runs every `.oxy` script in `dir`, in file name order.
+pub fn run_directory(dir: &Path) -> io::Result<Vec<GoldenResult>> {
+    let mut scripts: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    scripts.retain(|path| path.extension().is_some_and(|extension| extension == "oxy"));
+    scripts.sort();
+    scripts.iter().map(|script| run_script(script)).collect()
+}
+
+/// Runs one script and compares its output with the expectation files beside it. Errors are
+/// rendered with the bare file name, so expectations don't depend on where the corpus lives.
+pub fn run_script(script: &Path) -> io::Result<GoldenResult> {
+    let source = fs::read_to_string(script)?;
+    let file_name = script.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+    let (stdout, stderr) = with_large_stack(|| {
+        let stdout = SharedBuffer::default();
+        let mut stderr = Vec::new();
+        run_source(&source, &file_name, false, Box::new(stdout.clone()), &mut stderr);
+        (stdout.contents(), stderr)
+    });
+
+    let mut mismatches = Vec::new();
+    for (stream, actual) in [("stdout", stdout), ("stderr", String::from_utf8_lossy(&stderr).into_owned())] {
+        let expected = match fs::read_to_string(script.with_extension(stream)) {
+            Ok(expected) => expected,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error),
+        };
+        if expected != actual {
+            mismatches.push(Mismatch { stream, expected, actual });
+        }
+    }
+    Ok(GoldenResult { script: script.to_path_buf(), mismatches })
+}
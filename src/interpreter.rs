@@ -0,0 +1,99 @@
+use std::cell::Cell;
+use std::io::Write;
+use crate::tokenizer::Tokenizer;
+use crate::AstTree::ASTParser;
+use crate::runtime::Runtime;
+use crate::typecheck::TypeChecker;
+use crate::diagnostics;
+
+/// Native stack for the thread that runs scripts. A script-level call nests several Rust
+/// frames, which are large in unoptimized builds, so the default thread stack would overflow
+/// long before `DEFAULT_RECURSION_LIMIT` calls. Only the part actually used is committed.
+pub const STACK_SIZE: usize = 1 << 30;
+
+/// Native stack kept free below the last call the runtime allows: enough for whatever one call
+/// does before the next one is checked, however deeply its body nests.
+const STACK_RESERVE: usize = 64 << 20;
+
+thread_local! {
+    /// The lowest stack address a new call may start at, or 0 off the interpreter thread.
+    static STACK_FLOOR: Cell<usize> = const { Cell::new(0) };
+}
+
+/// An address in the caller's stack frame.
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/// Whether the interpreter thread's stack is too close to its end for another call. How much
+/// stack a call takes depends on its body and on the build, so `MAX_RECURSION_LIMIT` alone
+/// can't promise the stack lasts; the runtime checks this as well.
+pub fn stack_exhausted() -> bool {
+    let floor = STACK_FLOOR.get();
+    floor != 0 && stack_address() < floor
+}
+
+/// Runs `task` on a thread with a `STACK_SIZE` stack and waits for its result.
+pub fn with_large_stack<T: Send>(task: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        let handle = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                // The stack grows down from about here.
+                STACK_FLOOR.set(stack_address().saturating_sub(STACK_SIZE - STACK_RESERVE));
+                task()
+            })
+            .expect("could not start the interpreter thread");
+        handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Lexes, parses, type-checks and, unless `check_only` is set, runs `source`. Program output
+/// goes to `output`; errors are rendered against `file_name` and written to `errors`. Returns
+/// whether the script got through without an error. Call it through `with_large_stack` so that
+/// deep recursion reaches the recursion limit before the end of the native stack.
+pub fn run_source(source: &str, file_name: &str, check_only: bool, output: Box<dyn Write>, errors: &mut dyn Write) -> bool {
+    let tokens = match Tokenizer::new().process_content(source) {
+        Ok(tokens) => tokens,
+        Err(error) => {
+            let _ = writeln!(errors, "{}\n", diagnostics::render(source, file_name, error.span, &error.message));
+            return false;
+        }
+    };
+
+    let statements = match ASTParser::new().parse(tokens) {
+        Ok(statements) => statements,
+        Err(parse_errors) => {
+            for error in &parse_errors {
+                let _ = writeln!(errors, "{}\n", diagnostics::render(source, file_name, error.span, &error.message));
+            }
+            return false;
+        }
+    };
+
+    if let Err(type_errors) = TypeChecker::new().check(&statements) {
+        for error in &type_errors {
+            let _ = writeln!(errors, "{}\n", diagnostics::render(source, file_name, error.span, &error.message));
+        }
+        return false;
+    }
+
+    if check_only {
+        return true;
+    }
+
+    let mut runtime = Runtime::with_output(output);
+    let outcome = runtime.execute_statements(statements).and_then(|_| runtime.flush_output());
+    if let Err(error) = outcome {
+        let _ = runtime.flush_output();
+        let traceback = runtime.take_traceback();
+        if !traceback.is_empty() {
+            let _ = write!(errors, "{}", diagnostics::render_traceback(source, file_name, &traceback, &error));
+        }
+        let _ = writeln!(errors, "{}", diagnostics::render_runtime_error(source, file_name, &error));
+        return false;
+    }
+    true
+}
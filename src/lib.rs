@@ -0,0 +1,14 @@
+#![allow(non_snake_case, non_camel_case_types, clippy::upper_case_acronyms, clippy::enum_variant_names, clippy::new_without_default)]
+
+pub mod tokenizer;
+pub mod values;
+pub mod AstTree;
+pub mod Environment;
+pub mod runtime;
+pub mod Functions;
+pub mod Methods;
+pub mod Repl;
+pub mod diagnostics;
+pub mod typecheck;
+pub mod interpreter;
+pub mod golden;
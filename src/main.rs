@@ -1,108 +1,70 @@
 use std::fs;
-use std::io;
+use std::path::Path;
 
-mod tokenizer;
-mod AstTree;
-mod Environment;
-mod runtime;
-mod Functions;
-mod Repl;
-
-use tokenizer::Tokenizer;
-use AstTree::ASTParser;
-use runtime::Runtime;
+use OxyPy::{golden, interpreter, Repl};
 
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = std::env::args().collect();
     
     if args.len() == 1 {
-        Repl::start_repl();
-    }
-
-    if args.len() > 1 && args[1] == "--test" {
-        return Ok(());
-    }
-    
-    if args.len() < 2 {
-        eprintln!("Usage: {} <filename> or {} --test", args[0], args[0]);
-        return Ok(());
-    }
-    
-    let file_name = &args[1];
-
-    let tokenizer = Tokenizer::new();
-    let mut parser = ASTParser::new();
-    let mut runtime = Runtime::new();
-    
-    let file_content = fs::read_to_string(file_name)?;
-    
-    let cleaned_content = remove_comments(&file_content);
-    
-    if cleaned_content.trim().is_empty() {
+        interpreter::with_large_stack(Repl::start_repl);
         return Ok(());
     }
-    
-    let tokens = tokenizer.process_content(&cleaned_content);
-    
-    let statements = parser.parse(tokens);
-    runtime.execute_statements(statements);
 
-    Ok(())
-}
-
-fn remove_comments(content: &str) -> String {
-    let mut result = String::new();
-    let mut in_string = false;
-    let mut string_char = '"';
-    let mut escaped = false;
-    let mut chars = content.chars().peekable();
-    
-    while let Some(ch) = chars.next() {
-        if escaped {
-            result.push(ch);
-            escaped = false;
-            continue;
-        }
-        
-        if in_string {
-            result.push(ch);
-            if ch == '\\' {
-                escaped = true;
-            } else if ch == string_char {
-                in_string = false;
+    // `OxyPy --test dir/` runs the golden scripts in `dir` and reports each one.
+    if args[1] == "--test" {
+        let Some(dir) = args.get(2) else {
+            eprintln!("Usage: {} --test <directory>", args[0]);
+            return Ok(());
+        };
+        let results = match golden::run_directory(Path::new(dir)) {
+            Ok(results) => results,
+            Err(error) => {
+                eprintln!("error: cannot read test directory '{}': {}", dir, error);
+                std::process::exit(1);
             }
-            continue;
-        }
-        
-        match ch {
-            '"' | '\'' => {
-                in_string = true;
-                string_char = ch;
-                result.push(ch);
-            }
-            '/' => {
-                if let Some(&'/') = chars.peek() {
-                    chars.next(); 
-                    
-                    while let Some(next_ch) = chars.next() {
-                        if next_ch == '\n' {
-                            result.push('\n'); 
-                            break;
-                        }
-                    }
-                } else {
-                    result.push(ch);
+        };
+        let failed = results.iter().filter(|result| !result.passed()).count();
+        for result in &results {
+            if result.passed() {
+                println!("PASS {}", result.script.display());
+            } else {
+                println!("FAIL {}", result.script.display());
+                for mismatch in &result.mismatches {
+                    println!("{}", mismatch);
                 }
             }
-            _ => {
-                result.push(ch);
-            }
         }
+        println!("\n{} passed, {} failed", results.len() - failed, failed);
+        if failed > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
     }
     
-    result
-}
+    // `OxyPy check file.oxy` stops after the static type check.
+    let check_only = args[1] == "check";
+    let file_name = if check_only { args.get(2) } else { args.get(1) };
+    let Some(file_name) = file_name else {
+        eprintln!("Usage: {0} <filename>, {0} check <filename> or {0} --test <directory>", args[0]);
+        return Ok(());
+    };
 
+    let file_content = match fs::read_to_string(file_name) {
+        Ok(content) => content,
+        Err(error) => {
+            eprintln!("error: cannot read '{}': {}", file_name, error);
+            std::process::exit(1);
+        }
+    };
 
+    let succeeded = interpreter::with_large_stack(|| {
+        interpreter::run_source(&file_content, file_name, check_only, Box::new(std::io::stdout()), &mut std::io::stderr())
+    });
+    if !succeeded {
+        std::process::exit(1);
+    }
 
+    Ok(())
+}
@@ -1,790 +1,1599 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::time::Instant;
-use crate::tokenizer::{ClassInstance, DataHolder, Types};
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+use crate::tokenizer::{Span, Types};
+use crate::values::{Callable, ClassInstance, DataHolder, UserFunction};
 use crate::Environment::Environment;
-use crate::AstTree::{Statement, AstExpressions, FunctionParameter};
-use crate::Functions::get_built_in_functions;
+use crate::AstTree::{class_attributes, Statement, AstExpressions, FunctionParameter, KeywordArgument};
+use crate::Functions::{display_element, get_built_in_functions};
+use crate::Methods::get_native_methods;
+use crate::interpreter;
 
 
 #[derive(Debug, Clone)]
 pub enum ExecutionResult {
-    None,
+    /// The statement finished and execution moves on to the next one.
+    Normal,
     Return(DataHolder),
+    /// A `break` is unwinding to the nearest enclosing loop.
+    Break,
+    /// A `continue` is unwinding to the nearest enclosing loop, which starts its next iteration.
     Continue,
 }
 
 
-#[derive(Debug, Clone)]
-pub struct MethodContext {
-    pub instance: DataHolder, 
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    UndefinedClass(String),
+    TypeMismatch(String),
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Arguments that don't fit a function's parameters, such as a missing argument or an
+    /// unknown keyword. `problem` follows the function name, e.g. "is missing argument 'y'".
+    ArgumentMismatch {
+        name: String,
+        problem: String,
+    },
+    DivisionByZero,
+    UnknownMethod {
+        type_name: String,
+        method: String,
+    },
+    UnknownField {
+        type_name: String,
+        field: String,
+    },
+    /// An instance literal that leaves out some of the class's fields, or a new instance whose
+    /// class or function fields were never given a value.
+    MissingFields {
+        type_name: String,
+        fields: Vec<String>,
+    },
+    /// A class or function field read before anything was assigned to it.
+    UnsetField {
+        type_name: String,
+        field: String,
+    },
+    InvalidOperation(String),
+    IndexOutOfRange {
+        index: i64,
+        length: usize,
+    },
+    /// An integer operation whose result doesn't fit in the operands' type.
+    IntegerOverflow {
+        symbol: String,
+        data_type: Types,
+    },
+    /// A map lookup for a key that isn't there. Holds the key as `print` shows it in a list.
+    KeyNotFound(String),
+    /// A call that would nest deeper than the runtime's recursion limit, which it holds.
+    RecursionLimit(usize),
+    /// Another error tagged with the location of the innermost statement or expression that raised it.
+    At {
+        span: Span,
+        error: Box<RuntimeError>,
+    },
+}
+
+impl RuntimeError {
+    /// Attaches `span` unless the error already carries a more precise location.
+    pub fn at(self, span: Span) -> RuntimeError {
+        match self {
+            RuntimeError::At { .. } => self,
+            error => RuntimeError::At { span, error: Box::new(error) },
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::At { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// A value of type `actual` where `context` (e.g. "variable 'x'") requires `expected`.
+    pub fn wrong_type(context: &str, expected: &Types, actual: &Types) -> RuntimeError {
+        RuntimeError::TypeMismatch(format!("{} must be {}, got {}", context, expected, actual))
+    }
+
+    pub fn overflow(symbol: &str, data_type: &Types) -> RuntimeError {
+        RuntimeError::IntegerOverflow { symbol: symbol.to_string(), data_type: data_type.clone() }
+    }
+
+    pub fn argument_mismatch(name: &str, problem: String) -> RuntimeError {
+        RuntimeError::ArgumentMismatch { name: name.to_string(), problem }
+    }
+
+    /// A non-bool operand of `and` or `or`. `right` is `None` when the left operand was already
+    /// wrong, as the right one is then never evaluated.
+    pub fn logical_operand(keyword: &str, left: &Types, right: Option<&Types>) -> RuntimeError {
+        match right {
+            Some(right) => RuntimeError::TypeMismatch(format!("'{}' expects bool operands, got {} and {}", keyword, left, right)),
+            None => RuntimeError::TypeMismatch(format!("'{}' expects bool operands, got {} on the left", keyword, left)),
+        }
+    }
+
+    pub fn unsupported_operands(symbol: &str, left: &Types, right: &Types) -> RuntimeError {
+        RuntimeError::TypeMismatch(format!(
+            "unsupported operand types for {}: {} and {}",
+            symbol, left, right
+        ))
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::UndefinedVariable(name) => write!(f, "undefined variable '{}'", name),
+            RuntimeError::UndefinedFunction(name) => write!(f, "undefined function '{}'", name),
+            RuntimeError::UndefinedClass(name) => write!(f, "undefined class '{}'", name),
+            RuntimeError::TypeMismatch(message) => write!(f, "type mismatch: {}", message),
+            RuntimeError::ArityMismatch { name, expected, found } => {
+                write!(f, "'{}' expects {} argument(s), got {}", name, expected, found)
+            },
+            RuntimeError::ArgumentMismatch { name, problem } => write!(f, "'{}' {}", name, problem),
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+            RuntimeError::UnknownMethod { type_name, method } => {
+                write!(f, "'{}' has no method '{}'", type_name, method)
+            },
+            RuntimeError::UnknownField { type_name, field } => {
+                write!(f, "'{}' has no field '{}'", type_name, field)
+            },
+            RuntimeError::MissingFields { type_name, fields } => {
+                let noun = if fields.len() == 1 { "field" } else { "fields" };
+                write!(f, "'{}' is missing {} {}", type_name, noun, quoted_list(fields))
+            },
+            RuntimeError::UnsetField { type_name, field } => {
+                write!(f, "field '{}' of '{}' is read before it is set", field, type_name)
+            },
+            RuntimeError::InvalidOperation(message) => write!(f, "{}", message),
+            RuntimeError::IndexOutOfRange { index, length } => {
+                write!(f, "index {} out of range for length {}", index, length)
+            },
+            RuntimeError::IntegerOverflow { symbol, data_type } => {
+                write!(f, "integer overflow: result of '{}' does not fit in {}", symbol, data_type)
+            },
+            RuntimeError::KeyNotFound(key) => write!(f, "key {} not found in map", key),
+            RuntimeError::RecursionLimit(limit) => {
+                write!(f, "recursion limit exceeded: more than {} nested calls", limit)
+            },
+            RuntimeError::At { error, .. } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// `'a'`, `'a' and 'b'` or `'a', 'b' and 'c'`.
+fn quoted_list(names: &[String]) -> String {
+    let quoted: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+    match quoted.split_last() {
+        Some((last, others)) if !others.is_empty() => format!("{} and {}", others.join(", "), last),
+        _ => quoted.join(""),
+    }
+}
+
+/// `left == right`. Mixed numbers are promoted first, lists and maps compare by contents and
+/// instances by identity. List methods such as `contains` and map key lookups use it too, so
+/// they agree with the operator.
+pub fn values_equal(left: &DataHolder, right: &DataHolder) -> bool {
+    let promoted = Runtime::promote_operands(left, right);
+    let (left, right) = match &promoted {
+        Some((left, right)) => (left, right),
+        None => (left, right),
+    };
+
+    match (left, right) {
+        (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => a == b,
+        (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => a == b,
+        (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => (a - b).abs() < f32::EPSILON,
+        (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => (a - b).abs() < f64::EPSILON,
+        (DataHolder::STRING(a), DataHolder::STRING(b)) => a == b,
+        (DataHolder::BOOLEAN(a), DataHolder::BOOLEAN(b)) => a == b,
+        (DataHolder::FUNCTION(a), DataHolder::FUNCTION(b)) => a == b,
+        // Instances are shared references: equal only when both name the same one.
+        (DataHolder::CLASSINSTANCE(a), DataHolder::CLASSINSTANCE(b)) => Rc::ptr_eq(a, b),
+        (DataHolder::LIST(a), DataHolder::LIST(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| values_equal(x, y))
+        },
+        // Maps are equal when they hold the same keys with equal values, whatever order the
+        // keys were added in.
+        (DataHolder::MAP(a), DataHolder::MAP(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, x)| map_lookup(b, key).is_some_and(|y| values_equal(x, y)))
+        },
+        _ => false,
+    }
+}
+
+/// The value stored under `key`, matching keys with `values_equal`.
+pub fn map_lookup<'a>(entries: &'a [(DataHolder, DataHolder)], key: &DataHolder) -> Option<&'a DataHolder> {
+    entries.iter().find(|(existing, _)| values_equal(existing, key)).map(|(_, value)| value)
 }
 
 
+/// How deeply function calls may nest before a call fails with `RecursionLimit`.
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+/// The highest recursion limit a script may set. A call whose body needs a lot of native stack
+/// can still run out before this depth; `interpreter::stack_exhausted` stops it first.
+pub const MAX_RECURSION_LIMIT: usize = 10_000;
+
+/// A function call in progress: the function's name and where it was called from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    pub call_site: Span,
+}
+
+/// The evaluated arguments of a call: positional ones in order, then `name: value` ones.
+#[derive(Debug, Clone, Default)]
+pub struct CallArguments {
+    pub positional: Vec<DataHolder>,
+    pub keywords: Vec<(String, DataHolder)>,
+}
+
+/// Where a parameter gets its value in one call, as worked out by `Runtime::match_arguments`.
+#[derive(Debug, Clone)]
+pub enum ArgumentSource<'a> {
+    /// The positional argument at this index.
+    Positional(usize),
+    /// The keyword argument at this index.
+    Keyword(usize),
+    /// The parameter was left out, so it takes this default.
+    Default(&'a AstExpressions),
+    /// A rest parameter, collecting the positional arguments from this index on.
+    Rest(usize),
+}
+
 #[derive(Debug, Clone)]
-pub struct UserFunction {
-    pub name: String,
-    pub params: Vec<FunctionParameter>,
-    pub body: Vec<Statement>,
-    pub is_method: bool, 
+pub struct MethodContext {
+    pub instance: DataHolder,
 }
 
+/// Where an assignment or a mutating method stores its result, with the object and index
+/// expressions that lead there already evaluated.
+enum Place<'a> {
+    Variable { name: &'a str, expression: &'a AstExpressions },
+    Field(DataHolder, &'a str),
+    Element(Box<Place<'a>>, DataHolder),
+    /// A value that isn't stored anywhere, such as a call's result.
+    Temporary(DataHolder),
+}
+
+
 pub struct Runtime {
-    environment: Environment,
-    functions: HashMap<String, UserFunction>,
+    /// The innermost scope of whatever is currently executing.
+    environment: Rc<RefCell<Environment>>,
+    /// Module-level scope; every function and method call starts a new scope on top of it.
+    globals: Rc<RefCell<Environment>>,
     returning: bool,
     return_value: Option<DataHolder>,
-    method_context: Option<MethodContext>, 
+    method_context: Option<MethodContext>,
+    /// Where `print` and `println` write: stdout, or a buffer when output is captured.
+    output: Box<dyn Write>,
+    /// The calls in progress, outermost first. Kept by the runtime itself so that runaway
+    /// recursion ends in a `RecursionLimit` error rather than overflowing the native stack.
+    frames: Vec<Frame>,
+    recursion_limit: usize,
+    /// Span of the call expression being dispatched, recorded as the next frame's call site.
+    call_site: Span,
+    /// The frames that were active when the last error was raised, for its traceback.
+    traceback: Vec<Frame>,
 }
 
 impl Runtime {
     pub fn new() -> Self {
+        Self::with_output(Box::new(std::io::stdout()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
         Runtime {
-            environment: Environment::new(),
-            functions: HashMap::new(),
+            environment: Rc::clone(&globals),
+            globals,
             returning: false,
             return_value: None,
             method_context: None,
+            output,
+            frames: Vec::new(),
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            call_site: Span::default(),
+            traceback: Vec::new(),
         }
     }
 
-    pub fn execute_statements(&mut self, statements: Vec<Statement>) {
+    /// Sets how deeply calls may nest, up to `MAX_RECURSION_LIMIT`.
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+        self.recursion_limit = limit.min(MAX_RECURSION_LIMIT);
+    }
+
+    /// The calls that were in progress when the last error was raised, outermost first. Empty
+    /// when the error was raised outside any function.
+    pub fn take_traceback(&mut self) -> Vec<Frame> {
+        std::mem::take(&mut self.traceback)
+    }
+
+    /// Flushes anything the output is still buffering.
+    pub fn flush_output(&mut self) -> Result<(), RuntimeError> {
+        self.output.flush()
+            .map_err(|error| RuntimeError::InvalidOperation(format!("could not write output: {}", error)))
+    }
+
+    pub fn execute_statements(&mut self, statements: Vec<Statement>) -> Result<(), RuntimeError> {
+        self.traceback.clear();
         for statement in statements.iter() {
-            self.execute_statement(statement.clone());
-            
+            self.execute_statement(statement.clone())?;
+
             if self.returning {
                 break;
             }
         }
+        Ok(())
+    }
+
+    pub fn execute_statement(&mut self, statement: Statement) -> Result<ExecutionResult, RuntimeError> {
+        let span = statement.span();
+        self.execute_statement_at(statement).map_err(|error| error.at(span))
     }
 
-    pub fn execute_statement(&mut self, statement: Statement) -> ExecutionResult {
-        
+    fn execute_statement_at(&mut self, statement: Statement) -> Result<ExecutionResult, RuntimeError> {
+
         if self.returning {
-            return ExecutionResult::Return(self.return_value.clone().unwrap_or(DataHolder::INTEGER32(0)));
+            return Ok(ExecutionResult::Return(self.return_value.clone().unwrap_or(DataHolder::INTEGER32(0))));
         }
 
         match statement {
-            Statement::ClassMeta { name, fields } => {
-                self.environment.set_class(name.clone(), Statement::ClassMeta { name: name.clone(), fields: fields.clone() });
-                ExecutionResult::Continue
+            Statement::ClassMeta { name, fields, span } => {
+                self.environment.borrow_mut().set_class(name.clone(), Statement::ClassMeta { name: name.clone(), fields: fields.clone(), span });
+                Ok(ExecutionResult::Normal)
             },
 
-            Statement::VariableDeclaration { name, data_type: _, value } => {
-                if let Some(evaluated_value) = self.evaluate_expression(&value) {
-                    self.environment.set_variable(name.clone(), evaluated_value);
-                } else {
-                    println!("ERROR: Failed to evaluate expression for variable '{}'", name);
-                }
-                ExecutionResult::Continue
-            },
-            
-            Statement::ListDeclaration { name: _, elements: _, size: _ } => {
-                ExecutionResult::Continue
-            },
-            
-            Statement::Assignment { name, value } => {
-                if let Some(evaluated_value) = self.evaluate_expression(&value) {
-                    self.environment.set_variable(name, evaluated_value);
-                }
-                ExecutionResult::Continue
-            },
-            
-            
-            Statement::MemberAssignment { object, member, value } => {
-                if let Some(new_value) = self.evaluate_expression(&value) {
-                    if let AstExpressions::Variable { name: var_name } = object {
-                        if let Some(obj_value) = self.environment.get_variable(&var_name).cloned() {
-                            match obj_value {
-                                DataHolder::CLASSINSTANCE(mut instance) => {
-                                    instance.fields.insert(member.clone(), new_value);
-                                    self.environment.set_variable(var_name, DataHolder::CLASSINSTANCE(instance));
-                                },
-                                _ => {
-                                    eprintln!("Error: Cannot assign to member '{}' on non-object", member);
-                                }
-                            }
-                        }
-                    }
-                }
-                ExecutionResult::Continue
-            },
-            
-            Statement::Conditional { condition, then_branch, else_branch } => {
-                if let Some(condition_result) = self.evaluate_expression(&condition) {
-                    let should_execute_then = match condition_result {
-                        DataHolder::BOOLEAN(b) => b,
-                        DataHolder::INTEGER32(i) => i != 0,
-                        DataHolder::INTEGER64(i) => i != 0,
-                        DataHolder::FLOAT32(f) => f != 0.0,
-                        DataHolder::FLOAT64(f) => f != 0.0,
-                        DataHolder::STRING(s) => !s.is_empty(),
-                        DataHolder::LIST(list) => !list.is_empty(),
-                        _ => false,
-                    };
-                    
-                    if should_execute_then {
-                        for stmt in then_branch {
-                            let result = self.execute_statement(stmt);
-                            if matches!(result, ExecutionResult::Return(_)) {
-                                return result;
-                            }
-                        }
-                    } else if let Some(else_statements) = else_branch {
-                        for stmt in else_statements {
-                            let result = self.execute_statement(stmt);
-                            if matches!(result, ExecutionResult::Return(_)) {
-                                return result;
-                            }
-                        }
-                    }
+            Statement::VariableDeclaration { name, data_type, value, .. } => {
+                let evaluated_value = self.evaluate_expression(&value)?;
+                match data_type {
+                    Some(data_type) => {
+                        let evaluated_value = Self::conform_to_type(evaluated_value, &data_type, &format!("variable '{}'", name))?;
+                        self.environment.borrow_mut().declare_variable(name, evaluated_value, data_type);
+                    },
+                    None => self.environment.borrow_mut().set_variable(name, evaluated_value),
                 }
-                ExecutionResult::Continue
+                Ok(ExecutionResult::Normal)
             },
-            
-            Statement::Block(statements) => {
-                for stmt in statements {
-                    let result = self.execute_statement(stmt);
-                    if matches!(result, ExecutionResult::Return(_)) {
-                        return result;
-                    }
+
+            Statement::Assignment { name, value, .. } => {
+                let evaluated_value = self.evaluate_expression(&value)?;
+                self.assign_variable(name, evaluated_value)?;
+                Ok(ExecutionResult::Normal)
+            },
+
+
+            Statement::MemberAssignment { object, member, value, .. } => {
+                let new_value = self.evaluate_expression(&value)?;
+
+                // `object` may itself be a member access (`a.b.c = x`); evaluating it yields the
+                // shared instance, so writing the field updates it in place.
+                let obj_value = self.evaluate_expression(&object)?;
+                self.set_field(obj_value, &member, new_value)?;
+                Ok(ExecutionResult::Normal)
+            },
+
+            Statement::IndexAssignment { object, index, value, .. } => {
+                let new_value = self.evaluate_expression(&value)?;
+                let container = self.resolve_place(&object)?;
+                let index = self.evaluate_expression(&index)?;
+                self.store_place(&Place::Element(Box::new(container), index), new_value)?;
+                Ok(ExecutionResult::Normal)
+            },
+
+            // The target's object and index are evaluated once, for both the read and the write.
+            Statement::CompoundAssignment { target, operator, value, .. } => {
+                let place = self.resolve_place(&target)?;
+                let current = self.read_place(&place).map_err(|error| error.at(target.span()))?;
+                let operand = self.evaluate_expression(&value)?;
+                let result = self.perform_arithmetic_operation(&current, &operator, &operand)
+                    .map_err(|error| error.at(target.span().to(value.span())))?;
+                self.store_place(&place, result)?;
+                Ok(ExecutionResult::Normal)
+            },
+
+            Statement::Conditional { condition, then_branch, else_branch, .. } => {
+                let condition_result = self.evaluate_expression(&condition)?;
+
+                if Self::is_truthy(&condition_result) {
+                    self.execute_block(&then_branch)
+                } else if let Some(else_statements) = else_branch {
+                    self.execute_block(&else_statements)
+                } else {
+                    Ok(ExecutionResult::Normal)
                 }
-                ExecutionResult::Continue
-            },
-            
-            Statement::ExpressionStatement { expression } => {
-                self.evaluate_expression(&expression);
-                ExecutionResult::Continue
-            },
-            
-            Statement::ForLoop { variable, start, end, step, body } => {
-                let start_val = self.evaluate_expression(&start);
-                let end_val = self.evaluate_expression(&end);
-                let step_val = self.evaluate_expression(&step);
-                
-                if let (Some(start_num), Some(end_num), Some(step_num)) = (start_val, end_val, step_val) {
-                    match (start_num, end_num, step_num) {
-                        (DataHolder::INTEGER32(start), DataHolder::INTEGER32(end), DataHolder::INTEGER32(step)) => {
-                            let mut current = start;
-                            while (step > 0 && current < end) || (step < 0 && current > end) {
-                                self.environment.set_variable(variable.clone(), DataHolder::INTEGER32(current));
-
-                                for stmt in &body {
-                                    let result = self.execute_statement(stmt.clone());
-                                    if matches!(result, ExecutionResult::Return(_)) {
-                                        return result;
-                                    }
-                                }
-                                
-                                current += step;
-                            }
-                        },
-                        (DataHolder::INTEGER64(start), DataHolder::INTEGER64(end), DataHolder::INTEGER64(step)) => {
-                            let mut current = start;
-                            while (step > 0 && current < end) || (step < 0 && current > end) {
-                                self.environment.set_variable(variable.clone(), DataHolder::INTEGER64(current));
-                                
-                                for stmt in &body {
-                                    let result = self.execute_statement(stmt.clone());
-                                    if matches!(result, ExecutionResult::Return(_)) {
-                                        return result;
-                                    }
-                                }
-                                
-                                current += step;
-                            }
-                        },
-                        _ => {
-                            println!("For loop requires numeric values for start, end, and step");
-                        }
-                    }
+            },
+
+            Statement::Block { statements, .. } => {
+                self.execute_block(&statements)
+            },
+
+            Statement::ExpressionStatement { expression, .. } => {
+                self.evaluate_expression(&expression)?;
+                Ok(ExecutionResult::Normal)
+            },
+
+            Statement::ForLoop { variable, start, end, step, body, .. } => {
+                let start_val = self.evaluate_expression(&start)?;
+                let end_val = self.evaluate_expression(&end)?;
+                let step_val = self.evaluate_expression(&step)?;
+
+                match (start_val, end_val, step_val) {
+                    (DataHolder::INTEGER32(start), DataHolder::INTEGER32(end), DataHolder::INTEGER32(step)) => {
+                        self.run_counted_loop(&variable, start as i64, end as i64, step as i64, |n| DataHolder::INTEGER32(n as i32), &body)
+                    },
+                    (DataHolder::INTEGER64(start), DataHolder::INTEGER64(end), DataHolder::INTEGER64(step)) => {
+                        self.run_counted_loop(&variable, start, end, step, DataHolder::INTEGER64, &body)
+                    },
+                    (start, end, step) => {
+                        Err(RuntimeError::TypeMismatch(format!(
+                            "for loop range requires matching integer start, end and step, got {}, {} and {}",
+                            start.get_type(), end.get_type(), step.get_type()
+                        )))
+                    },
                 }
-                ExecutionResult::Continue
             },
 
-            Statement::WhileLoop { condition, body } => {
+            Statement::ForEach { variable, iterable, body, .. } => {
+                let items = Self::iterable_items(self.evaluate_expression(&iterable)?)?;
+                self.run_for_each(&variable, items, &body)
+            },
+
+            Statement::WhileLoop { condition, body, .. } => {
                 loop {
-                    if let Some(condition_result) = self.evaluate_expression(&condition) {
-                        let should_continue = match condition_result {
-                            DataHolder::BOOLEAN(b) => b,
-                            DataHolder::INTEGER32(i) => i != 0,
-                            DataHolder::INTEGER64(i) => i != 0,
-                            DataHolder::FLOAT32(f) => f != 0.0,
-                            DataHolder::FLOAT64(f) => f != 0.0,
-                            DataHolder::STRING(s) => !s.is_empty(),
-                            DataHolder::LIST(list) => !list.is_empty(),
-                            _ => false,
-                        };
-                        
-                        if !should_continue {
-                            break;
-                        }
-                        
-                        for stmt in &body {
-                            let result = self.execute_statement(stmt.clone());
-                            if matches!(result, ExecutionResult::Return(_)) {
-                                return result;
-                            }
-                        }
-                    } else {
+                    let condition_result = self.evaluate_expression(&condition)?;
+                    if !Self::is_truthy(&condition_result) {
                         break;
                     }
+
+                    match self.execute_block(&body)? {
+                        ExecutionResult::Break => break,
+                        result @ ExecutionResult::Return(_) => return Ok(result),
+                        ExecutionResult::Normal | ExecutionResult::Continue => {},
+                    }
                 }
-                ExecutionResult::Continue
+                Ok(ExecutionResult::Normal)
             },
 
-            Statement::FunctionDeclaration { name, params, body } => {
-                let user_function = UserFunction {
-                    name: name.clone(),
-                    params,
-                    body,
-                    is_method: false, 
-                };
-                self.functions.insert(name.clone(), user_function);
-                ExecutionResult::Continue
+            Statement::FunctionDeclaration { name, params, return_type, body, .. } => {
+                let function = self.make_function(UserFunction { name: name.clone(), params, return_type, body });
+                self.environment.borrow_mut().declare_variable(name, function, Types::FUNCTION);
+                Ok(ExecutionResult::Normal)
             },
-            
-            Statement::Return { value } => {
+
+            Statement::Return { value, .. } => {
                 let return_val = if let Some(expr) = value {
-                    self.evaluate_expression(&expr).unwrap_or(DataHolder::INTEGER32(0))
+                    self.evaluate_expression(&expr)?
                 } else {
-                    DataHolder::INTEGER32(0) 
+                    DataHolder::INTEGER32(0)
                 };
-                
+
                 self.returning = true;
                 self.return_value = Some(return_val.clone());
-                ExecutionResult::Return(return_val)
+                Ok(ExecutionResult::Return(return_val))
             },
-            
-            _ => {
-                println!("Unhandled statement: {:?}", statement);
-                ExecutionResult::Continue
+
+            Statement::Break { .. } => Ok(ExecutionResult::Break),
+
+            Statement::Continue { .. } => Ok(ExecutionResult::Continue),
+
+            Statement::ClassAttribute { name, .. } => {
+                Err(RuntimeError::InvalidOperation(format!("Class attribute '{}' declared outside of a class", name)))
             }
         }
     }
-    
-    
-    pub fn evaluate_expression(&mut self, expr: &AstExpressions) -> Option<DataHolder> {
-        match expr {
-            AstExpressions::Value { value } => Some(value.clone()),
-            
-            AstExpressions::Variable { name } => {
-                
-                if name == "self" {
-                    if let Some(context) = &self.method_context {
-                        return Some(context.instance.clone());
-                    }
+
+    fn child_scope(&self) -> Rc<RefCell<Environment>> {
+        Rc::new(RefCell::new(Environment::new_child(Rc::clone(&self.environment))))
+    }
+
+    /// Runs `statements` in a fresh scope nested inside the current one.
+    fn execute_block(&mut self, statements: &[Statement]) -> Result<ExecutionResult, RuntimeError> {
+        let scope = self.child_scope();
+        self.execute_in_scope(statements, scope)
+    }
+
+    /// Runs `statements` with `scope` as the current environment, restoring the previous
+    /// environment afterwards even if a statement fails.
+    fn execute_in_scope(&mut self, statements: &[Statement], scope: Rc<RefCell<Environment>>) -> Result<ExecutionResult, RuntimeError> {
+        let previous = std::mem::replace(&mut self.environment, scope);
+        let mut outcome = Ok(ExecutionResult::Normal);
+
+        for stmt in statements {
+            match self.execute_statement(stmt.clone()) {
+                Ok(ExecutionResult::Normal) => {},
+                result => {
+                    outcome = result;
+                    break;
+                }
+            }
+        }
+
+        self.environment = previous;
+        outcome
+    }
+
+    /// Rebinds the nearest visible variable called `name`, which may live in an enclosing block
+    /// or in the module globals. If no scope has it, it is declared in the current scope.
+    /// Use `let` to shadow a global with a local instead.
+    fn assign_variable(&mut self, name: String, value: DataHolder) -> Result<(), RuntimeError> {
+        let declared_type = self.environment.borrow().get_variable_type(&name);
+        let value = match declared_type {
+            Some(data_type) => Self::conform_to_type(value, &data_type, &format!("variable '{}'", name))?,
+            None => value,
+        };
+
+        let mut environment = self.environment.borrow_mut();
+        if !environment.assign_variable(&name, value.clone()) {
+            environment.set_variable(name, value);
+        }
+        Ok(())
+    }
+
+    /// Checks `value` against a declared type, widening it when `Types::accepts` allows.
+    fn conform_to_type(value: DataHolder, expected: &Types, context: &str) -> Result<DataHolder, RuntimeError> {
+        match (value, expected) {
+            (DataHolder::INTEGER32(n), Types::INTEGER64) => Ok(DataHolder::INTEGER64(n as i64)),
+            (DataHolder::INTEGER32(n), Types::FLOAT64) => Ok(DataHolder::FLOAT64(n as f64)),
+            (DataHolder::FLOAT32(n), Types::FLOAT64) => Ok(DataHolder::FLOAT64(n as f64)),
+            (DataHolder::FLOAT64(n), Types::FLOAT32) => Ok(DataHolder::FLOAT32(n as f32)),
+            (value, expected) if value.get_type() == *expected => Ok(value),
+            (value, expected) => Err(RuntimeError::wrong_type(context, expected, &value.get_type())),
+        }
+    }
+
+    fn get_field(&self, object: &DataHolder, member: &str) -> Result<DataHolder, RuntimeError> {
+        match object {
+            DataHolder::CLASSINSTANCE(instance) => {
+                let instance = instance.borrow();
+                if let Some(value) = instance.fields.get(member) {
+                    return Ok(value.clone());
+                }
+                let type_name = instance.class_name.clone();
+                let field = member.to_string();
+                match self.field_type(&type_name, member) {
+                    Some(_) => Err(RuntimeError::UnsetField { type_name, field }),
+                    None => Err(RuntimeError::UnknownField { type_name, field }),
                 }
-                self.environment.get_variable(name).cloned()
             },
-            
-            AstExpressions::Literal { value } => {
-                Some(DataHolder::STRING(value.clone()))
+            other => Err(RuntimeError::UnknownField {
+                type_name: other.get_type().to_string(),
+                field: member.to_string(),
+            }),
+        }
+    }
+
+    fn set_field(&mut self, object: DataHolder, member: &str, value: DataHolder) -> Result<(), RuntimeError> {
+        match object {
+            DataHolder::CLASSINSTANCE(instance) => {
+                let class_name = instance.borrow().class_name.clone();
+                let Some(field_type) = self.field_type(&class_name, member) else {
+                    return Err(RuntimeError::UnknownField { type_name: class_name, field: member.to_string() });
+                };
+                let value = Self::conform_to_type(value, &field_type, &format!("field '{}' of '{}'", member, class_name))?;
+                instance.borrow_mut().fields.insert(member.to_string(), value);
+                Ok(())
+            },
+            other => {
+                Err(RuntimeError::TypeMismatch(format!("cannot assign to member '{}' on value of type {}", member, other.get_type())))
+            }
+        }
+    }
+
+    /// What `for` loops over: a list's elements, a string's characters or a map's keys.
+    pub fn iterable_items(value: DataHolder) -> Result<Vec<DataHolder>, RuntimeError> {
+        match value {
+            DataHolder::LIST(list) => Ok(list),
+            DataHolder::STRING(text) => Ok(text.chars().map(|ch| DataHolder::STRING(ch.to_string())).collect()),
+            DataHolder::MAP(entries) => Ok(entries.into_iter().map(|(key, _)| key).collect()),
+            other => Err(RuntimeError::TypeMismatch(format!("value of type {} is not iterable", other.get_type()))),
+        }
+    }
+
+    /// Runs `body` for each number from `start` up to (or down to) `end`, exclusive, each time
+    /// in a new scope with `variable` bound to the number. Counting is done in i64 and `wrap`
+    /// turns each number back into the loop's own integer type, whose range it stays within.
+    fn run_counted_loop(&mut self, variable: &str, start: i64, end: i64, step: i64, wrap: fn(i64) -> DataHolder, body: &[Statement]) -> Result<ExecutionResult, RuntimeError> {
+        let mut current = start;
+        while (step > 0 && current < end) || (step < 0 && current > end) {
+            let loop_scope = self.child_scope();
+            loop_scope.borrow_mut().set_variable(variable.to_string(), wrap(current));
+
+            match self.execute_in_scope(body, loop_scope)? {
+                ExecutionResult::Break => break,
+                result @ ExecutionResult::Return(_) => return Ok(result),
+                ExecutionResult::Normal | ExecutionResult::Continue => {},
+            }
+
+            // A step past the type's limit is also past `end`, as in `range`.
+            match current.checked_add(step) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        Ok(ExecutionResult::Normal)
+    }
+
+    /// Runs `body` once per item, each time in a new scope with `variable` bound to the item.
+    fn run_for_each(&mut self, variable: &str, items: Vec<DataHolder>, body: &[Statement]) -> Result<ExecutionResult, RuntimeError> {
+        for item in items {
+            let loop_scope = self.child_scope();
+            loop_scope.borrow_mut().set_variable(variable.to_string(), item);
+
+            match self.execute_in_scope(body, loop_scope)? {
+                ExecutionResult::Break => break,
+                result @ ExecutionResult::Return(_) => return Ok(result),
+                ExecutionResult::Normal | ExecutionResult::Continue => {},
+            }
+        }
+        Ok(ExecutionResult::Normal)
+    }
+
+    /// Evaluates the parts of `target` that pick out where it is stored: the object of a member
+    /// access, and the container and index of an element. Anything else is a `Temporary`.
+    fn resolve_place<'a>(&mut self, target: &'a AstExpressions) -> Result<Place<'a>, RuntimeError> {
+        match target {
+            AstExpressions::Variable { name, .. } => Ok(Place::Variable { name, expression: target }),
+            AstExpressions::MemberAccess { object, member, .. } => {
+                Ok(Place::Field(self.evaluate_expression(object)?, member))
+            },
+            AstExpressions::Index { object, index, .. } => {
+                let container = self.resolve_place(object)?;
+                let index = self.evaluate_expression(index)?;
+                Ok(Place::Element(Box::new(container), index))
+            },
+            AstExpressions::Grouping { expression, .. } => self.resolve_place(expression),
+            other => Ok(Place::Temporary(self.evaluate_expression(other)?)),
+        }
+    }
+
+    fn read_place(&mut self, place: &Place) -> Result<DataHolder, RuntimeError> {
+        match place {
+            Place::Variable { expression, .. } => self.evaluate_expression(expression),
+            Place::Field(object, member) => self.get_field(object, member),
+            Place::Element(container, index) => {
+                let container = self.read_place(container)?;
+                Self::index_into(container, index.clone())
+            },
+            Place::Temporary(value) => Ok(value.clone()),
+        }
+    }
+
+    /// Stores `value` at `place`. Lists and maps are values rather than references, so an
+    /// element is written into a copy of its container, which is then stored back where the
+    /// container came from: a variable, a field or an element of an outer list or map.
+    /// Assigning to a missing map key adds it.
+    fn store_place(&mut self, place: &Place, value: DataHolder) -> Result<(), RuntimeError> {
+        match place {
+            Place::Variable { name, .. } => self.assign_variable(name.to_string(), value),
+            Place::Field(object, member) => self.set_field(object.clone(), member, value),
+            Place::Element(container, index) => {
+                let updated = match self.read_place(container)? {
+                    DataHolder::LIST(mut list) => {
+                        let position = Self::resolve_index(Self::index_value(index)?, list.len())?;
+                        list[position] = value;
+                        DataHolder::LIST(list)
+                    },
+                    DataHolder::MAP(mut entries) => {
+                        let key = Self::map_key(index.clone())?;
+                        match entries.iter_mut().find(|(existing, _)| values_equal(existing, &key)) {
+                            Some((_, slot)) => *slot = value,
+                            None => entries.push((key, value)),
+                        }
+                        DataHolder::MAP(entries)
+                    },
+                    other => {
+                        return Err(RuntimeError::TypeMismatch(format!("cannot assign to an index of value of type {}", other.get_type())));
+                    },
+                };
+                self.store_place(container, updated)
+            },
+            Place::Temporary(_) => Err(RuntimeError::InvalidOperation("cannot assign to this expression".to_string())),
+        }
+    }
+
+    /// Calls a method on a built-in value. When the method changes the value and it was read
+    /// from a variable, field or element, the changed value is stored back there.
+    fn call_native_method(&mut self, place: &Place, mut receiver: DataHolder, method: &str, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+        let native = get_native_methods().get(&receiver.get_type(), method).ok_or_else(|| RuntimeError::UnknownMethod {
+            type_name: receiver.get_type().to_string(),
+            method: method.to_string(),
+        })?;
+
+        let result = (native.function)(&mut receiver, args)?;
+        if native.mutates && !matches!(place, Place::Temporary(_)) {
+            self.store_place(place, receiver)?;
+        }
+        Ok(result)
+    }
+
+    /// Checks that `value` can be used as a map key.
+    pub fn map_key(value: DataHolder) -> Result<DataHolder, RuntimeError> {
+        match value {
+            DataHolder::STRING(_) | DataHolder::INTEGER32(_) | DataHolder::INTEGER64(_) | DataHolder::BOOLEAN(_) => Ok(value),
+            other => Err(RuntimeError::TypeMismatch(format!("map keys must be strings, integers or bools, got {}", other.get_type()))),
+        }
+    }
+
+    /// `container[index]` for a list, string or map.
+    fn index_into(container: DataHolder, index: DataHolder) -> Result<DataHolder, RuntimeError> {
+        match container {
+            DataHolder::LIST(list) => {
+                let position = Self::resolve_index(Self::index_value(&index)?, list.len())?;
+                Ok(list[position].clone())
+            },
+            DataHolder::STRING(text) => {
+                let chars: Vec<char> = text.chars().collect();
+                let position = Self::resolve_index(Self::index_value(&index)?, chars.len())?;
+                Ok(DataHolder::STRING(chars[position].to_string()))
+            },
+            DataHolder::MAP(entries) => {
+                let key = Self::map_key(index)?;
+                map_lookup(&entries, &key)
+                    .cloned()
+                    .ok_or_else(|| RuntimeError::KeyNotFound(display_element(&key)))
+            },
+            other => Err(RuntimeError::TypeMismatch(format!("value of type {} cannot be indexed", other.get_type()))),
+        }
+    }
+
+    pub fn index_value(value: &DataHolder) -> Result<i64, RuntimeError> {
+        match value {
+            DataHolder::INTEGER32(n) => Ok(*n as i64),
+            DataHolder::INTEGER64(n) => Ok(*n),
+            other => Err(RuntimeError::TypeMismatch(format!("indices must be integers, got {}", other.get_type()))),
+        }
+    }
+
+    /// Turns a possibly negative index into a position; `-1` is the last element.
+    pub fn resolve_index(index: i64, length: usize) -> Result<usize, RuntimeError> {
+        let position = if index < 0 { index + length as i64 } else { index };
+        if position < 0 || position >= length as i64 {
+            Err(RuntimeError::IndexOutOfRange { index, length })
+        } else {
+            Ok(position as usize)
+        }
+    }
+
+    /// Slice bounds work as in Python: negative bounds count from the end, bounds past either
+    /// end are clamped, and a start beyond the end gives an empty slice.
+    pub fn resolve_slice(start: Option<i64>, end: Option<i64>, length: usize) -> (usize, usize) {
+        let clamp = |bound: i64| {
+            let bound = if bound < 0 { bound + length as i64 } else { bound };
+            bound.clamp(0, length as i64) as usize
+        };
+        let from = start.map_or(0, clamp);
+        let to = end.map_or(length, clamp);
+        (from, to.max(from))
+    }
+
+    fn field_type(&self, class_name: &str, field: &str) -> Option<Types> {
+        match self.environment.borrow().get_class(class_name) {
+            Some(Statement::ClassMeta { fields, .. }) => match fields.get(field) {
+                Some(Statement::ClassAttribute { data_type, .. }) => Some(data_type.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Pairs each parameter of `function_name` other than `self` with where its value comes from
+    /// in a call with `positional` positional arguments and the given keywords. Positional
+    /// arguments fill parameters in order and keywords fill them by name; a default covers a
+    /// parameter left out, and a rest parameter takes the positional arguments left over.
+    /// The type checker uses this too, so both report the same problems.
+    pub fn match_arguments<'a>(function_name: &str, params: &'a [FunctionParameter], positional: usize, keywords: &[&str]) -> Result<Vec<(&'a FunctionParameter, ArgumentSource<'a>)>, RuntimeError> {
+        let params: Vec<_> = params.iter().filter(|param| param.name != "self").collect();
+        let (fixed, rest) = match params.split_last() {
+            Some((last, others)) if last.rest => (others, Some(*last)),
+            _ => (&params[..], None),
+        };
+
+        if positional > fixed.len() && rest.is_none() {
+            return Err(RuntimeError::argument_mismatch(function_name, format!(
+                "takes {} positional argument(s), got {}", fixed.len(), positional
+            )));
+        }
+
+        let mut sources: Vec<_> = (0..fixed.len())
+            .map(|index| (index < positional).then_some(ArgumentSource::Positional(index)))
+            .collect();
+        for (keyword_index, keyword) in keywords.iter().enumerate() {
+            let Some(index) = fixed.iter().position(|param| param.name == *keyword) else {
+                return Err(RuntimeError::argument_mismatch(function_name, format!("got an unexpected keyword argument '{}'", keyword)));
+            };
+            if sources[index].is_some() {
+                return Err(RuntimeError::argument_mismatch(function_name, format!("got multiple values for argument '{}'", keyword)));
+            }
+            sources[index] = Some(ArgumentSource::Keyword(keyword_index));
+        }
+
+        let missing: Vec<String> = fixed.iter().zip(&sources)
+            .filter(|(param, source)| source.is_none() && param.default.is_none())
+            .map(|(param, _)| param.name.clone())
+            .collect();
+        if !missing.is_empty() {
+            let noun = if missing.len() == 1 { "argument" } else { "arguments" };
+            return Err(RuntimeError::argument_mismatch(function_name, format!("is missing {} {}", noun, quoted_list(&missing))));
+        }
+
+        let mut matched: Vec<_> = fixed.iter().zip(sources)
+            .map(|(param, source)| match (source, &param.default) {
+                (Some(source), _) => (*param, source),
+                (None, default) => (*param, ArgumentSource::Default(default.as_ref().expect("missing arguments were reported above"))),
+            })
+            .collect();
+        if let Some(rest) = rest {
+            matched.push((rest, ArgumentSource::Rest(fixed.len().min(positional))));
+        }
+        Ok(matched)
+    }
+
+    /// Declares the parameters of `function` in the current scope, which is the new call's
+    /// scope, each checked against its type. Defaults are evaluated here, in order, so a
+    /// default can use the parameters before it.
+    fn bind_arguments(&mut self, function: &UserFunction, matched: Vec<(&FunctionParameter, ArgumentSource)>, args: CallArguments) -> Result<(), RuntimeError> {
+        for (param, source) in matched {
+            let value = match source {
+                ArgumentSource::Positional(index) => args.positional[index].clone(),
+                ArgumentSource::Keyword(index) => args.keywords[index].1.clone(),
+                ArgumentSource::Default(default) => self.evaluate_expression(default)?,
+                ArgumentSource::Rest(start) => DataHolder::LIST(args.positional[start..].to_vec()),
+            };
+            let context = format!("parameter '{}' of '{}'", param.name, function.name);
+            let value = Self::conform_to_type(value, &param.data_type, &context)?;
+            self.environment.borrow_mut().declare_variable(param.name.clone(), value, param.data_type.clone());
+        }
+        Ok(())
+    }
+
+    /// Checks the field names of an instance literal against the fields `class_name`
+    /// declares: each must be given, and nothing else. The type checker uses this too.
+    pub fn check_literal_fields(class_name: &str, declared: &[String], given: &[&str]) -> Result<(), RuntimeError> {
+        if let Some(unknown) = given.iter().find(|field| !declared.iter().any(|name| name == *field)) {
+            return Err(RuntimeError::UnknownField { type_name: class_name.to_string(), field: unknown.to_string() });
+        }
+        let missing: Vec<String> = declared.iter()
+            .filter(|name| !given.contains(&name.as_str()))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            return Err(RuntimeError::MissingFields { type_name: class_name.to_string(), fields: missing });
+        }
+        Ok(())
+    }
+
+    fn evaluate_arguments(&mut self, arguments: &[AstExpressions], keyword_arguments: &[KeywordArgument]) -> Result<CallArguments, RuntimeError> {
+        let mut evaluated = CallArguments::default();
+        for argument in arguments {
+            evaluated.positional.push(self.evaluate_expression(argument)?);
+        }
+        for (name, argument) in keyword_arguments {
+            evaluated.keywords.push((name.clone(), self.evaluate_expression(argument)?));
+        }
+        Ok(evaluated)
+    }
+
+    /// The value a call evaluates to, checked against the function's `-> type` annotation.
+    /// A function without an annotation that never reaches `return` yields 0.
+    fn check_return_value(function_name: &str, return_type: Option<&Types>, returned: Option<DataHolder>) -> Result<DataHolder, RuntimeError> {
+        match (return_type, returned) {
+            (None, returned) => Ok(returned.unwrap_or(DataHolder::INTEGER32(0))),
+            (Some(expected), Some(value)) => {
+                Self::conform_to_type(value, expected, &format!("return value of '{}'", function_name))
+            },
+            (Some(expected), None) => Err(RuntimeError::TypeMismatch(format!(
+                "'{}' must return a value of type {}", function_name, expected
+            ))),
+        }
+    }
+
+    fn is_truthy(value: &DataHolder) -> bool {
+        match value {
+            DataHolder::BOOLEAN(b) => *b,
+            DataHolder::INTEGER32(i) => *i != 0,
+            DataHolder::INTEGER64(i) => *i != 0,
+            DataHolder::FLOAT32(f) => *f != 0.0,
+            DataHolder::FLOAT64(f) => *f != 0.0,
+            DataHolder::STRING(s) => !s.is_empty(),
+            DataHolder::LIST(list) => !list.is_empty(),
+            DataHolder::MAP(entries) => !entries.is_empty(),
+            _ => false,
+        }
+    }
+
+
+    pub fn evaluate_expression(&mut self, expr: &AstExpressions) -> Result<DataHolder, RuntimeError> {
+        self.evaluate_expression_at(expr).map_err(|error| error.at(expr.span()))
+    }
+
+    fn evaluate_expression_at(&mut self, expr: &AstExpressions) -> Result<DataHolder, RuntimeError> {
+        match expr {
+            AstExpressions::Value { value, .. } => Ok(value.clone()),
+
+            AstExpressions::Variable { name, .. } => {
+
+                if name == "self"
+                    && let Some(context) = &self.method_context {
+                    return Ok(context.instance.clone());
+                }
+                if let Some(value) = self.environment.borrow().get_variable(name) {
+                    return Ok(value);
+                }
+                // A builtin's name, used as a value rather than called.
+                match get_built_in_functions().lock() {
+                    Ok(functions) if functions.has_function(name) => {
+                        Ok(DataHolder::FUNCTION(Rc::new(Callable::BuiltIn(name.clone()))))
+                    },
+                    _ => Err(RuntimeError::UndefinedVariable(name.clone())),
+                }
             },
-            
-            AstExpressions::BinaryOperation { left, operator, right } => {
+
+            AstExpressions::BinaryOperation { left, operator, right, .. } => {
                 let left_val = self.evaluate_expression(left)?;
                 let right_val = self.evaluate_expression(right)?;
                 self.perform_arithmetic_operation(&left_val, operator, &right_val)
             },
-            
-            AstExpressions::UnaryOperation { operator, operand } => {
+
+            AstExpressions::UnaryOperation { operator, operand, .. } => {
                 let operand_val = self.evaluate_expression(operand)?;
-                match operator {
-                    
-                    crate::tokenizer::ArithmeticOperator::Not => {
-                        match operand_val {
-                            DataHolder::BOOLEAN(b) => Some(DataHolder::BOOLEAN(!b)),
-                            DataHolder::INTEGER32(i) => Some(DataHolder::BOOLEAN(i == 0)),
-                            DataHolder::INTEGER64(i) => Some(DataHolder::BOOLEAN(i == 0)),
-                            _ => None,
-                        }
-                    },
-                    _ => self.perform_unary_operation(operator, &operand_val)
-                }
+                self.perform_unary_operation(operator, &operand_val)
             },
-            
-            AstExpressions::ComparisonOperation { left, operator, right } => {
+
+            AstExpressions::ComparisonOperation { left, operator, right, .. } => {
                 let left_val = self.evaluate_expression(left)?;
                 let right_val = self.evaluate_expression(right)?;
                 self.perform_comparison_operation(&left_val, operator, &right_val)
             },
-            
-            AstExpressions::LogicalOperation { left, operator, right } => {
-                let left_val = self.evaluate_expression(left)?;
-                
-                match operator {
-                    crate::tokenizer::LogicalOperator::And => {
-                        if let DataHolder::BOOLEAN(false) = left_val {
-                            Some(DataHolder::BOOLEAN(false)) 
-                        } else {
-                            let right_val = self.evaluate_expression(right)?;
-                            match (left_val, right_val) {
-                                (DataHolder::BOOLEAN(a), DataHolder::BOOLEAN(b)) => 
-                                    Some(DataHolder::BOOLEAN(a && b)),
-                                _ => None,
-                            }
-                        }
-                    },
-                    crate::tokenizer::LogicalOperator::Or => {
-                        if let DataHolder::BOOLEAN(true) = left_val {
-                            Some(DataHolder::BOOLEAN(true)) 
-                        } else {
-                            let right_val = self.evaluate_expression(right)?;
-                            match (left_val, right_val) {
-                                (DataHolder::BOOLEAN(a), DataHolder::BOOLEAN(b)) => 
-                                    Some(DataHolder::BOOLEAN(a || b)),
-                                _ => None,
-                            }
-                        }
-                    },
+
+            AstExpressions::LogicalOperation { left, operator, right, .. } => {
+                let keyword = match operator {
+                    crate::tokenizer::LogicalOperator::And => "and",
+                    crate::tokenizer::LogicalOperator::Or => "or",
+                };
+                // The left operand's type is checked before the right one runs, so a bad left
+                // operand never triggers the right one's side effects.
+                let left_val = match self.evaluate_expression(left)? {
+                    DataHolder::BOOLEAN(value) => value,
+                    other => return Err(RuntimeError::logical_operand(keyword, &other.get_type(), None)),
+                };
+                let short_circuits = match operator {
+                    crate::tokenizer::LogicalOperator::And => !left_val,
+                    crate::tokenizer::LogicalOperator::Or => left_val,
+                };
+                if short_circuits {
+                    return Ok(DataHolder::BOOLEAN(left_val));
+                }
+                match self.evaluate_expression(right)? {
+                    DataHolder::BOOLEAN(right_val) => Ok(DataHolder::BOOLEAN(right_val)),
+                    other => Err(RuntimeError::logical_operand(keyword, &Types::BOOLEAN, Some(&other.get_type()))),
                 }
             },
-            
-            AstExpressions::ListLiteral { elements } => {
+
+            AstExpressions::ListLiteral { elements, .. } => {
                 let mut evaluated_elements = Vec::new();
                 for element in elements {
-                    if let Some(val) = self.evaluate_expression(element) {
-                        evaluated_elements.push(val);
-                    } else {
-                        return None;
-                    }
+                    evaluated_elements.push(self.evaluate_expression(element)?);
                 }
-                Some(DataHolder::LIST(evaluated_elements))
+                Ok(DataHolder::LIST(evaluated_elements))
             },
-            
-            AstExpressions::MemberAccess { object, member } => {
-                let obj_value = self.evaluate_expression(object)?;
-                
-                match obj_value {
-                    DataHolder::CLASSINSTANCE(ref instance) => {
-                        
-                        instance.fields.get(member).cloned()
-                    },
-                    _ => {
-                        eprintln!("Error: Cannot access member '{}' on non-object", member);
-                        None
+
+            AstExpressions::MapLiteral { entries, .. } => {
+                let mut evaluated_entries: Vec<(DataHolder, DataHolder)> = Vec::new();
+                for (key, value) in entries {
+                    let key = Self::map_key(self.evaluate_expression(key)?)?;
+                    let value = self.evaluate_expression(value)?;
+                    match evaluated_entries.iter_mut().find(|(existing, _)| values_equal(existing, &key)) {
+                        Some((_, slot)) => *slot = value,
+                        None => evaluated_entries.push((key, value)),
                     }
                 }
+                Ok(DataHolder::MAP(evaluated_entries))
             },
 
-            AstExpressions::MethodCall { object, method, arguments } => {
-                let obj_value = self.evaluate_expression(object);
-                
-                if obj_value.is_none() {
-                    if let AstExpressions::Variable { name } = object.as_ref() { 
-                        eprintln!("Variable '{}' not found in environment", name);
-                        eprintln!("Available variables: {:?}", self.environment.get_all_variables().keys().collect::<Vec<_>>());
-                    }
-                    return None;
-                }
-                
-                let obj_value = obj_value.unwrap();
-                
+            AstExpressions::MemberAccess { object, member, .. } => {
+                let obj_value = self.evaluate_expression(object)?;
+                self.get_field(&obj_value, member)
+            },
+
+            AstExpressions::MethodCall { object, method, arguments, keyword_arguments, span } => {
+                // Resolved once, so a mutating method like `xs[i()].push(x)` stores back to the
+                // element it read.
+                let place = self.resolve_place(object)?;
+                let obj_value = self.read_place(&place).map_err(|error| error.at(object.span()))?;
+                let evaluated_args = self.evaluate_arguments(arguments, keyword_arguments)?;
+                self.call_site = *span;
+
                 match obj_value {
                     DataHolder::CLASSINSTANCE(ref instance) => {
-
-                        let mut evaluated_args = Vec::new();
-                        for arg in arguments {
-                            if let Some(val) = self.evaluate_expression(arg) {
-                                evaluated_args.push(val);
-                            } else {
-                                return None;
-                            }
+                        // A field holding a function is called like a method, without `self`.
+                        let field = instance.borrow().fields.get(method).cloned();
+                        if let Some(callee @ DataHolder::FUNCTION(_)) = field {
+                            return self.call_with_arguments(&callee, evaluated_args);
+                        }
+                        let class_name = instance.borrow().class_name.clone();
+                        if field.is_none() && self.field_type(&class_name, method) == Some(Types::FUNCTION) {
+                            return Err(RuntimeError::UnsetField { type_name: class_name, field: method.clone() });
                         }
-                        
-                        self.call_method(&instance.class_name, method, obj_value.clone(), evaluated_args)
+                        self.call_method(&class_name, method, obj_value.clone(), evaluated_args)
+                    },
+                    receiver => {
+                        if !evaluated_args.keywords.is_empty() {
+                            let name = format!("{}.{}", receiver.get_type(), method);
+                            return Err(RuntimeError::argument_mismatch(&name, "does not take keyword arguments".to_string()));
+                        }
+                        self.call_native_method(&place, receiver, method, evaluated_args.positional)
                     },
-                    _ => {
-                        eprintln!("Error: Cannot call method '{}' on non-object: {:?}", method, obj_value);
-                        None
-                    }
                 }
             },
 
-            AstExpressions::FunctionCall { name, arguments } => {
+            AstExpressions::FunctionCall { name, arguments, keyword_arguments, span } => {
+
+                let is_class = self.environment.borrow().is_class_meta_exists(name);
 
-                let is_class = self.environment.is_class_meta_exists(name);
-                
                 if is_class {
-                    let result = self.create_class_instance(name, arguments);
-                    return result;
-                }
-                let mut evaluated_args = Vec::new();
-                for arg in arguments {
-                    if let Some(val) = self.evaluate_expression(arg) {
-                        evaluated_args.push(val);
-                    } else {
-                        return None;
-                    }
+                    return self.create_class_instance(name, arguments, keyword_arguments, *span);
                 }
-                self.call_function(name, evaluated_args)
+                let evaluated_args = self.evaluate_arguments(arguments, keyword_arguments)?;
+                self.call_site = *span;
+                let callee = self.environment.borrow().get_variable(name);
+                match callee {
+                    Some(callee) => self.call_with_arguments(&callee, evaluated_args),
+                    None => self.execute_builtin_function(name, evaluated_args),
+                }
+            },
+
+            AstExpressions::Call { callee, arguments, keyword_arguments, span } => {
+                let callee = self.evaluate_expression(callee)?;
+                let evaluated_args = self.evaluate_arguments(arguments, keyword_arguments)?;
+                self.call_site = *span;
+                self.call_with_arguments(&callee, evaluated_args)
+            },
+
+            AstExpressions::InstanceLiteral { class_name, fields, .. } => self.build_instance(class_name, fields),
+
+            AstExpressions::Lambda { params, return_type, body, .. } => {
+                Ok(self.make_function(UserFunction {
+                    name: "lambda".to_string(),
+                    params: params.clone(),
+                    return_type: return_type.clone(),
+                    body: body.clone(),
+                }))
             },
 
-            AstExpressions::Grouping { expression } => {
+            AstExpressions::Grouping { expression, .. } => {
                 self.evaluate_expression(expression)
             },
+
+            AstExpressions::Index { object, index, .. } => {
+                let obj_value = self.evaluate_expression(object)?;
+                let index = self.evaluate_expression(index)?;
+                Self::index_into(obj_value, index)
+            },
+
+            AstExpressions::Slice { object, start, end, .. } => {
+                let obj_value = self.evaluate_expression(object)?;
+                let start = match start {
+                    Some(start) => Some(Self::index_value(&self.evaluate_expression(start)?)?),
+                    None => None,
+                };
+                let end = match end {
+                    Some(end) => Some(Self::index_value(&self.evaluate_expression(end)?)?),
+                    None => None,
+                };
+
+                match obj_value {
+                    DataHolder::LIST(list) => {
+                        let (from, to) = Self::resolve_slice(start, end, list.len());
+                        Ok(DataHolder::LIST(list[from..to].to_vec()))
+                    },
+                    DataHolder::STRING(text) => {
+                        let chars: Vec<char> = text.chars().collect();
+                        let (from, to) = Self::resolve_slice(start, end, chars.len());
+                        Ok(DataHolder::STRING(chars[from..to].iter().collect()))
+                    },
+                    other => Err(RuntimeError::TypeMismatch(format!("value of type {} cannot be sliced", other.get_type()))),
+                }
+            },
         }
     }
-    
-    
-    fn create_class_instance(&mut self, class_name: &str, arguments: &Vec<AstExpressions>) -> Option<DataHolder> {
-        if let Some(class_def) = self.environment.get_class(class_name) {
-            if let Statement::ClassMeta { name, fields } = class_def {
-                let mut instance_fields = HashMap::new();
-                
-                
-                for (field_name, field_stmt) in fields.iter() {
-                    match field_stmt {
-                        Statement::ClassAttribute { name: _, data_type } => {
-                            let default_value = self.get_default_value(data_type);
-                            instance_fields.insert(field_name.clone(), default_value);
-                        },
-                        Statement::FunctionDeclaration { .. } => {
-                            
-                            continue;
-                        },
-                        _ => continue,
-                    }
-                }
-                
-                let mut instance = DataHolder::CLASSINSTANCE(ClassInstance {
-                    class_name: name.clone(),
-                    fields: instance_fields,
-                });
-                
-                
-                if fields.contains_key("__init__") {
-                    let mut evaluated_args = Vec::new();
-                    for arg in arguments {
-                        if let Some(val) = self.evaluate_expression(arg) {
-                            evaluated_args.push(val);
-                        } else {
-                            return None;
-                        }
-                    }
-                    
-                    
-                    self.call_method(class_name, "__init__", instance.clone(), evaluated_args);
-                }
-                
-                return Some(instance);
+
+
+    fn create_class_instance(&mut self, class_name: &str, arguments: &[AstExpressions], keyword_arguments: &[KeywordArgument], span: Span) -> Result<DataHolder, RuntimeError> {
+        let Some(Statement::ClassMeta { name, fields, .. }) = self.environment.borrow().get_class(class_name) else {
+            return Err(RuntimeError::UndefinedClass(class_name.to_string()));
+        };
+        let mut instance_fields = HashMap::new();
+
+        // Class and function fields have no default; `__init__` has to set them.
+        for (field_name, field_stmt) in fields.iter() {
+            if let Statement::ClassAttribute { data_type, .. } = field_stmt
+                && let Some(default_value) = Self::get_default_value(data_type)
+            {
+                instance_fields.insert(field_name.clone(), default_value);
             }
         }
-        
-        eprintln!("Error: Could not instantiate class '{}'", class_name);
-        None
-    }
-
-    
-    fn call_method(&mut self, class_name: &str, method_name: &str, instance: DataHolder, args: Vec<DataHolder>) -> Option<DataHolder> {
-        
-        if let Some(class_def) = self.environment.get_class(class_name).cloned() {
-            if let Statement::ClassMeta { fields, .. } = class_def {
-                
-                if let Some(Statement::FunctionDeclaration { name, params, body }) = fields.get(method_name) {
-                    
-                    let method_params = params.clone();
-                    let method_body = body.clone();
-                    
-                    
-                    let old_context = self.method_context.take();
-                    self.method_context = Some(MethodContext {
-                        instance: instance.clone(),
-                    });
-                    
-                    
-                    let mut method_env = Environment::new();
-                    
-                    
-                    let current_vars: Vec<(String, DataHolder)> = self.environment
-                        .get_all_variables()
-                        .iter()
-                        .map(|(k, v)| (k.clone(), v.clone()))
-                        .collect();
-                
-                for (key, value) in current_vars {
-                    method_env.set_variable(key, value);
-                }
-                
-                
-                method_env.set_variable("self".to_string(), instance);
-                
-                
-                let non_self_params: Vec<_> = method_params.iter()
-                    .filter(|param| param.name != "self")
-                    .collect();
-                
-                
-                if args.len() != non_self_params.len() {
-                    eprintln!("Method '{}' expects {} arguments, got {}", method_name, non_self_params.len(), args.len());
-                    self.method_context = old_context;
-                    return None;
-                }
-                
-                
-                for (param, arg) in non_self_params.iter().zip(args.iter()) {
-                    method_env.set_variable(param.name.clone(), arg.clone());
-                }
-                
-                
-                let old_env = std::mem::replace(&mut self.environment, method_env);
-                let old_returning = self.returning;
-                let old_return_value = self.return_value.clone();
-                
-                
-                self.returning = false;
-                self.return_value = None;
-                
-                
-                for statement in method_body.iter() {
-                    let result = self.execute_statement(statement.clone());
-                    if matches!(result, ExecutionResult::Return(_)) {
-                        break;
-                    }
-                }
-                
-                
-                let return_val = self.return_value.clone().unwrap_or(DataHolder::INTEGER32(0));
-                
-                
-                self.environment = old_env;
-                self.returning = old_returning;
-                self.return_value = old_return_value;
-                self.method_context = old_context;
-                
-                return Some(return_val);
+
+        let instance = DataHolder::CLASSINSTANCE(Rc::new(RefCell::new(ClassInstance {
+            class_name: name.clone(),
+            fields: instance_fields,
+        })));
+
+
+        // `__init__` gets this same instance, so the fields it sets are kept.
+        if fields.contains_key("__init__") {
+            let evaluated_args = self.evaluate_arguments(arguments, keyword_arguments)?;
+            self.call_site = span;
+            self.call_method(class_name, "__init__", instance.clone(), evaluated_args)?;
+        } else if !arguments.is_empty() || !keyword_arguments.is_empty() {
+            return Err(RuntimeError::argument_mismatch(class_name, "takes no arguments, as it has no '__init__'".to_string()));
+        }
+
+        if let DataHolder::CLASSINSTANCE(created) = &instance {
+            let created = created.borrow();
+            let unset: Vec<String> = class_attributes(&fields).into_iter()
+                .map(|(field, _)| field)
+                .filter(|field| !created.fields.contains_key(field))
+                .collect();
+            if !unset.is_empty() {
+                return Err(RuntimeError::MissingFields { type_name: name, fields: unset });
             }
         }
+
+        Ok(instance)
     }
-    
-    eprintln!("Error: Method '{}' not found in class '{}'", method_name, class_name);
-    None
-}
-    
-    fn get_default_value(&self, data_type: &Types) -> DataHolder {
+
+    /// `Class { field: value, ... }`: an instance with exactly the declared fields, each
+    /// checked against its type. `__init__` is not run.
+    fn build_instance(&mut self, class_name: &str, fields: &[(String, AstExpressions)]) -> Result<DataHolder, RuntimeError> {
+        let Some(Statement::ClassMeta { name, fields: members, .. }) = self.environment.borrow().get_class(class_name) else {
+            return Err(RuntimeError::UndefinedClass(class_name.to_string()));
+        };
+        let attributes = class_attributes(&members);
+        let declared: Vec<String> = attributes.iter().map(|(field, _)| field.clone()).collect();
+        let given: Vec<&str> = fields.iter().map(|(field, _)| field.as_str()).collect();
+        Self::check_literal_fields(&name, &declared, &given)?;
+
+        let mut instance_fields = HashMap::new();
+        for (field, value) in fields {
+            let Some((_, data_type)) = attributes.iter().find(|(declared, _)| declared == field) else {
+                continue;
+            };
+            let value_span = value.span();
+            let value = self.evaluate_expression(value)?;
+            let value = Self::conform_to_type(value, data_type, &format!("field '{}' of '{}'", field, name))
+                .map_err(|error| error.at(value_span))?;
+            instance_fields.insert(field.clone(), value);
+        }
+
+        Ok(DataHolder::CLASSINSTANCE(Rc::new(RefCell::new(ClassInstance {
+            class_name: name,
+            fields: instance_fields,
+        }))))
+    }
+
+
+    fn call_method(&mut self, class_name: &str, method_name: &str, instance: DataHolder, args: CallArguments) -> Result<DataHolder, RuntimeError> {
+
+        let method = match self.environment.borrow().get_class(class_name) {
+            Some(Statement::ClassMeta { fields, .. }) => match fields.get(method_name) {
+                Some(Statement::FunctionDeclaration { params, return_type, body, .. }) => {
+                    Some((params.clone(), return_type.clone(), body.clone()))
+                },
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some((params, return_type, body)) = method else {
+            return Err(RuntimeError::UnknownMethod {
+                type_name: class_name.to_string(),
+                method: method_name.to_string(),
+            });
+        };
+
+        let method = UserFunction { name: format!("{}.{}", class_name, method_name), params, return_type, body };
+        let globals = Rc::clone(&self.globals);
+        self.run_function(&method, globals, Some(MethodContext { instance }), args)
+    }
+
+    /// Runs a function or method body in a new scope inside `parent`, with the arguments bound
+    /// there. `context` is the instance `self` refers to, for a method. The call gets a frame
+    /// once its arguments are bound; a frame past the recursion limit is refused.
+    fn run_function(&mut self, function: &UserFunction, parent: Rc<RefCell<Environment>>, context: Option<MethodContext>, args: CallArguments) -> Result<DataHolder, RuntimeError> {
+        let call_site = self.call_site;
+        let mut scope = Environment::new_child(parent);
+        if let Some(context) = &context {
+            scope.set_variable("self".to_string(), context.instance.clone());
+        }
+
+        let old_context = std::mem::replace(&mut self.method_context, context);
+        let old_env = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(scope)));
+        let old_returning = self.returning;
+        let old_return_value = self.return_value.take();
+
+        self.returning = false;
+
+        // Arguments that don't fit the parameters are the caller's error, found before the
+        // frame is pushed. Default values are evaluated inside it: they may call functions too,
+        // and recursion through them has to hit the limit like any other.
+        let keywords: Vec<&str> = args.keywords.iter().map(|(name, _)| name.as_str()).collect();
+        let outcome = Self::match_arguments(&function.name, &function.params, args.positional.len(), &keywords).and_then(|matched| {
+            if self.frames.len() >= self.recursion_limit {
+                return Err(RuntimeError::RecursionLimit(self.recursion_limit));
+            }
+            if interpreter::stack_exhausted() {
+                return Err(RuntimeError::RecursionLimit(self.frames.len()));
+            }
+            self.frames.push(Frame { function: function.name.clone(), call_site });
+            let outcome = self.bind_arguments(function, matched, args).and_then(|_| self.execute_body(&function.body));
+            if outcome.is_err() && self.traceback.is_empty() {
+                self.traceback = self.frames.clone();
+            }
+            self.frames.pop();
+            outcome
+        });
+
+        let returned = self.return_value.take();
+        // A builtin may call several functions in turn, each from the builtin's own call site.
+        self.call_site = call_site;
+
+        self.environment = old_env;
+        self.returning = old_returning;
+        self.return_value = old_return_value;
+        self.method_context = old_context;
+
+        outcome?;
+        Self::check_return_value(&function.name, function.return_type.as_ref(), returned)
+    }
+
+    fn execute_body(&mut self, body: &[Statement]) -> Result<(), RuntimeError> {
+        for statement in body {
+            if let ExecutionResult::Return(value) = self.execute_statement(statement.clone())? {
+                self.return_value = Some(value);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The value a field of `data_type` starts with in a new instance. Class and function
+    /// fields have none and must be set by `__init__`. The type checker uses this too.
+    pub fn get_default_value(data_type: &Types) -> Option<DataHolder> {
         match data_type {
-            Types::INTEGER32 => DataHolder::INTEGER32(0),
-            Types::INTEGER64 => DataHolder::INTEGER64(0),
-            Types::FLOAT32 => DataHolder::FLOAT32(0.0),
-            Types::FLOAT64 => DataHolder::FLOAT64(0.0),
-            Types::BOOLEAN => DataHolder::BOOLEAN(false),
-            Types::STRING => DataHolder::STRING(String::new()),
-            Types::LIST => DataHolder::LIST(Vec::new()),
-            _ => DataHolder::INTEGER32(0),
+            Types::INTEGER32 => Some(DataHolder::INTEGER32(0)),
+            Types::INTEGER64 => Some(DataHolder::INTEGER64(0)),
+            Types::FLOAT32 => Some(DataHolder::FLOAT32(0.0)),
+            Types::FLOAT64 => Some(DataHolder::FLOAT64(0.0)),
+            Types::BOOLEAN => Some(DataHolder::BOOLEAN(false)),
+            Types::STRING => Some(DataHolder::STRING(String::new())),
+            Types::LIST => Some(DataHolder::LIST(Vec::new())),
+            Types::MAP => Some(DataHolder::MAP(Vec::new())),
+            Types::FUNCTION | Types::CLASS(_) => None,
+        }
+    }
+
+
+    /// Converts mixed numeric operands to their common type (see `Types::promote`). Returns
+    /// `None` when no conversion is needed or the operands aren't both numbers.
+    fn promote_operands(left: &DataHolder, right: &DataHolder) -> Option<(DataHolder, DataHolder)> {
+        let (left_type, right_type) = (left.get_type(), right.get_type());
+        if left_type == right_type {
+            return None;
+        }
+        let common = Types::promote(&left_type, &right_type)?;
+        Some((Self::convert_number(left, &common), Self::convert_number(right, &common)))
+    }
+
+    /// Converts a number to a numeric type at least as wide, as `promote_operands` needs.
+    fn convert_number(value: &DataHolder, target: &Types) -> DataHolder {
+        match (value, target) {
+            (DataHolder::INTEGER32(n), Types::INTEGER64) => DataHolder::INTEGER64(*n as i64),
+            (DataHolder::INTEGER32(n), Types::FLOAT32) => DataHolder::FLOAT32(*n as f32),
+            (DataHolder::INTEGER64(n), Types::FLOAT32) => DataHolder::FLOAT32(*n as f32),
+            (DataHolder::INTEGER32(n), Types::FLOAT64) => DataHolder::FLOAT64(*n as f64),
+            (DataHolder::INTEGER64(n), Types::FLOAT64) => DataHolder::FLOAT64(*n as f64),
+            (DataHolder::FLOAT32(n), Types::FLOAT64) => DataHolder::FLOAT64(*n as f64),
+            (value, _) => value.clone(),
         }
     }
 
-    
-    fn perform_arithmetic_operation(&self, left: &DataHolder, operator: &crate::tokenizer::ArithmeticOperator, right: &DataHolder) -> Option<DataHolder> {
+    pub fn perform_arithmetic_operation(&self, left: &DataHolder, operator: &crate::tokenizer::ArithmeticOperator, right: &DataHolder) -> Result<DataHolder, RuntimeError> {
+        let (left_type, right_type) = (left.get_type(), right.get_type());
+        let unsupported = |symbol: &str| RuntimeError::unsupported_operands(symbol, &left_type, &right_type);
+
+        let promoted = Self::promote_operands(left, right);
+        let (left, right) = match &promoted {
+            Some((left, right)) => (left, right),
+            None => (left, right),
+        };
+
         match operator {
             crate::tokenizer::ArithmeticOperator::Add => {
                 match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::INTEGER32(a + b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::INTEGER64(a + b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::FLOAT32(a + b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::FLOAT64(a + b)),
-                    (DataHolder::STRING(a), DataHolder::STRING(b)) => Some(DataHolder::STRING(format!("{}{}", a, b))),
-                    _ => None,
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => a.checked_add(*b).map(DataHolder::INTEGER32).ok_or_else(|| RuntimeError::overflow("+", &Types::INTEGER32)),
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => a.checked_add(*b).map(DataHolder::INTEGER64).ok_or_else(|| RuntimeError::overflow("+", &Types::INTEGER64)),
+                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Ok(DataHolder::FLOAT32(a + b)),
+                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Ok(DataHolder::FLOAT64(a + b)),
+                    (DataHolder::STRING(a), DataHolder::STRING(b)) => Ok(DataHolder::STRING(format!("{}{}", a, b))),
+                    _ => Err(unsupported("+")),
                 }
             },
             crate::tokenizer::ArithmeticOperator::Subtract => {
                 match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::INTEGER32(a - b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::INTEGER64(a - b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::FLOAT32(a - b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::FLOAT64(a - b)),
-                    _ => None,
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => a.checked_sub(*b).map(DataHolder::INTEGER32).ok_or_else(|| RuntimeError::overflow("-", &Types::INTEGER32)),
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => a.checked_sub(*b).map(DataHolder::INTEGER64).ok_or_else(|| RuntimeError::overflow("-", &Types::INTEGER64)),
+                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Ok(DataHolder::FLOAT32(a - b)),
+                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Ok(DataHolder::FLOAT64(a - b)),
+                    _ => Err(unsupported("-")),
                 }
             },
             crate::tokenizer::ArithmeticOperator::Multiply => {
                 match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::INTEGER32(a * b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::INTEGER64(a * b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::FLOAT32(a * b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::FLOAT64(a * b)),
-                    _ => None,
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => a.checked_mul(*b).map(DataHolder::INTEGER32).ok_or_else(|| RuntimeError::overflow("*", &Types::INTEGER32)),
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => a.checked_mul(*b).map(DataHolder::INTEGER64).ok_or_else(|| RuntimeError::overflow("*", &Types::INTEGER64)),
+                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Ok(DataHolder::FLOAT32(a * b)),
+                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Ok(DataHolder::FLOAT64(a * b)),
+                    _ => Err(unsupported("*")),
                 }
             },
             crate::tokenizer::ArithmeticOperator::Divide => {
                 match (left, right) {
                     (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => {
-                        if *b == 0 { None } else { Some(DataHolder::INTEGER32(a / b)) }
+                        if *b == 0 { Err(RuntimeError::DivisionByZero) } else { a.checked_div(*b).map(DataHolder::INTEGER32).ok_or_else(|| RuntimeError::overflow("/", &Types::INTEGER32)) }
                     },
                     (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => {
-                        if *b == 0 { None } else { Some(DataHolder::INTEGER64(a / b)) }
+                        if *b == 0 { Err(RuntimeError::DivisionByZero) } else { a.checked_div(*b).map(DataHolder::INTEGER64).ok_or_else(|| RuntimeError::overflow("/", &Types::INTEGER64)) }
                     },
                     (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => {
-                        if *b == 0.0 { None } else { Some(DataHolder::FLOAT32(a / b)) }
+                        if *b == 0.0 { Err(RuntimeError::DivisionByZero) } else { Ok(DataHolder::FLOAT32(a / b)) }
                     },
                     (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => {
-                        if *b == 0.0 { None } else { Some(DataHolder::FLOAT64(a / b)) }
+                        if *b == 0.0 { Err(RuntimeError::DivisionByZero) } else { Ok(DataHolder::FLOAT64(a / b)) }
                     },
-                    _ => None,
+                    _ => Err(unsupported("/")),
                 }
             },
+            // The result takes the sign of the left operand, for floats as for integers.
             crate::tokenizer::ArithmeticOperator::Modulo => {
                 match (left, right) {
                     (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => {
-                        if *b == 0 { None } else { Some(DataHolder::INTEGER32(a % b)) }
+                        if *b == 0 { Err(RuntimeError::DivisionByZero) } else { a.checked_rem(*b).map(DataHolder::INTEGER32).ok_or_else(|| RuntimeError::overflow("%", &Types::INTEGER32)) }
+                    },
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => {
+                        if *b == 0 { Err(RuntimeError::DivisionByZero) } else { a.checked_rem(*b).map(DataHolder::INTEGER64).ok_or_else(|| RuntimeError::overflow("%", &Types::INTEGER64)) }
+                    },
+                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => {
+                        if *b == 0.0 { Err(RuntimeError::DivisionByZero) } else { Ok(DataHolder::FLOAT32(a % b)) }
+                    },
+                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => {
+                        if *b == 0.0 { Err(RuntimeError::DivisionByZero) } else { Ok(DataHolder::FLOAT64(a % b)) }
+                    },
+                    _ => Err(unsupported("%")),
+                }
+            },
+            crate::tokenizer::ArithmeticOperator::Power => {
+                match (left, right) {
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => {
+                        Self::integer_exponent(*b as i64)?.and_then(|exponent| a.checked_pow(exponent)).map(DataHolder::INTEGER32).ok_or_else(|| RuntimeError::overflow("**", &Types::INTEGER32))
                     },
                     (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => {
-                        if *b == 0 { None } else { Some(DataHolder::INTEGER64(a % b)) }
+                        Self::integer_exponent(*b)?.and_then(|exponent| a.checked_pow(exponent)).map(DataHolder::INTEGER64).ok_or_else(|| RuntimeError::overflow("**", &Types::INTEGER64))
                     },
-                    _ => None,
+                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Ok(DataHolder::FLOAT32(a.powf(*b))),
+                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Ok(DataHolder::FLOAT64(a.powf(*b))),
+                    _ => Err(unsupported("**")),
                 }
             },
-            crate::tokenizer::ArithmeticOperator::Not => {
-                
-                None
+            crate::tokenizer::ArithmeticOperator::FloorDivide => {
+                match (left, right) {
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => {
+                        if *b == 0 { Err(RuntimeError::DivisionByZero) } else { Self::floor_divide(*a as i64, *b as i64).and_then(|n| i32::try_from(n).ok()).map(DataHolder::INTEGER32).ok_or_else(|| RuntimeError::overflow("~/", &Types::INTEGER32)) }
+                    },
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => {
+                        if *b == 0 { Err(RuntimeError::DivisionByZero) } else { Self::floor_divide(*a, *b).map(DataHolder::INTEGER64).ok_or_else(|| RuntimeError::overflow("~/", &Types::INTEGER64)) }
+                    },
+                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => {
+                        if *b == 0.0 { Err(RuntimeError::DivisionByZero) } else { Ok(DataHolder::FLOAT32((a / b).floor())) }
+                    },
+                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => {
+                        if *b == 0.0 { Err(RuntimeError::DivisionByZero) } else { Ok(DataHolder::FLOAT64((a / b).floor())) }
+                    },
+                    _ => Err(unsupported("~/")),
+                }
+            },
+            crate::tokenizer::ArithmeticOperator::BitAnd => {
+                match (left, right) {
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Ok(DataHolder::INTEGER32(a & b)),
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Ok(DataHolder::INTEGER64(a & b)),
+                    _ => Err(unsupported("&")),
+                }
+            },
+            crate::tokenizer::ArithmeticOperator::BitOr => {
+                match (left, right) {
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Ok(DataHolder::INTEGER32(a | b)),
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Ok(DataHolder::INTEGER64(a | b)),
+                    _ => Err(unsupported("|")),
+                }
+            },
+            crate::tokenizer::ArithmeticOperator::BitXor => {
+                match (left, right) {
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Ok(DataHolder::INTEGER32(a ^ b)),
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Ok(DataHolder::INTEGER64(a ^ b)),
+                    _ => Err(unsupported("^")),
+                }
+            },
+            // `>>` is an arithmetic shift: the sign bit is copied in from the left.
+            crate::tokenizer::ArithmeticOperator::ShiftLeft | crate::tokenizer::ArithmeticOperator::ShiftRight => {
+                let symbol = operator.symbol();
+                let shift_left = matches!(operator, crate::tokenizer::ArithmeticOperator::ShiftLeft);
+                let out_of_range = |amount: i64, data_type: &Types| {
+                    RuntimeError::InvalidOperation(format!("shift amount {} out of range for {}", amount, data_type))
+                };
+                match (left, right) {
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => {
+                        let shifted = u32::try_from(*b).ok().and_then(|amount| if shift_left { a.checked_shl(amount) } else { a.checked_shr(amount) });
+                        shifted.map(DataHolder::INTEGER32).ok_or_else(|| out_of_range(*b as i64, &Types::INTEGER32))
+                    },
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => {
+                        let shifted = u32::try_from(*b).ok().and_then(|amount| if shift_left { a.checked_shl(amount) } else { a.checked_shr(amount) });
+                        shifted.map(DataHolder::INTEGER64).ok_or_else(|| out_of_range(*b, &Types::INTEGER64))
+                    },
+                    _ => Err(unsupported(symbol)),
+                }
+            },
+            crate::tokenizer::ArithmeticOperator::Not | crate::tokenizer::ArithmeticOperator::BitNot => {
+                Err(RuntimeError::InvalidOperation(format!("'{}' is a unary operator", operator.symbol())))
             },
         }
     }
-    
-    fn perform_unary_operation(&self, operator: &crate::tokenizer::ArithmeticOperator, operand: &DataHolder) -> Option<DataHolder> {
+
+    /// An integer exponent as `checked_pow` takes it: `None` if too large to possibly fit.
+    fn integer_exponent(exponent: i64) -> Result<Option<u32>, RuntimeError> {
+        if exponent < 0 {
+            return Err(RuntimeError::InvalidOperation(format!(
+                "integer power with negative exponent {}; use a float base instead", exponent
+            )));
+        }
+        Ok(u32::try_from(exponent).ok())
+    }
+
+    /// Integer division rounded toward negative infinity. `None` on overflow.
+    fn floor_divide(a: i64, b: i64) -> Option<i64> {
+        let quotient = a.checked_div(b)?;
+        if a % b != 0 && (a < 0) != (b < 0) {
+            Some(quotient - 1)
+        } else {
+            Some(quotient)
+        }
+    }
+
+    fn perform_unary_operation(&self, operator: &crate::tokenizer::ArithmeticOperator, operand: &DataHolder) -> Result<DataHolder, RuntimeError> {
         match operator {
             crate::tokenizer::ArithmeticOperator::Subtract => {
                 match operand {
-                    DataHolder::INTEGER32(n) => Some(DataHolder::INTEGER32(-n)),
-                    DataHolder::INTEGER64(n) => Some(DataHolder::INTEGER64(-n)),
-                    DataHolder::FLOAT32(n) => Some(DataHolder::FLOAT32(-n)),
-                    DataHolder::FLOAT64(n) => Some(DataHolder::FLOAT64(-n)),
-                    _ => None,
+                    DataHolder::INTEGER32(n) => n.checked_neg().map(DataHolder::INTEGER32).ok_or_else(|| RuntimeError::overflow("-", &Types::INTEGER32)),
+                    DataHolder::INTEGER64(n) => n.checked_neg().map(DataHolder::INTEGER64).ok_or_else(|| RuntimeError::overflow("-", &Types::INTEGER64)),
+                    DataHolder::FLOAT32(n) => Ok(DataHolder::FLOAT32(-n)),
+                    DataHolder::FLOAT64(n) => Ok(DataHolder::FLOAT64(-n)),
+                    _ => Err(RuntimeError::TypeMismatch(format!("bad operand type for unary -: {}", operand.get_type()))),
                 }
             },
-            crate::tokenizer::ArithmeticOperator::Add => Some(operand.clone()),
-            
+            crate::tokenizer::ArithmeticOperator::Add => Ok(operand.clone()),
+
             crate::tokenizer::ArithmeticOperator::Not => {
                 match operand {
-                    DataHolder::BOOLEAN(b) => Some(DataHolder::BOOLEAN(!b)),
-                    DataHolder::INTEGER32(i) => Some(DataHolder::BOOLEAN(*i == 0)),
-                    DataHolder::INTEGER64(i) => Some(DataHolder::BOOLEAN(*i == 0)),
-                    _ => None,
+                    DataHolder::BOOLEAN(b) => Ok(DataHolder::BOOLEAN(!b)),
+                    DataHolder::INTEGER32(i) => Ok(DataHolder::BOOLEAN(*i == 0)),
+                    DataHolder::INTEGER64(i) => Ok(DataHolder::BOOLEAN(*i == 0)),
+                    _ => Err(RuntimeError::TypeMismatch(format!("bad operand type for !: {}", operand.get_type()))),
+                }
+            },
+            crate::tokenizer::ArithmeticOperator::BitNot => {
+                match operand {
+                    DataHolder::INTEGER32(n) => Ok(DataHolder::INTEGER32(!n)),
+                    DataHolder::INTEGER64(n) => Ok(DataHolder::INTEGER64(!n)),
+                    _ => Err(RuntimeError::TypeMismatch(format!("bad operand type for ~: {}", operand.get_type()))),
                 }
             },
-            _ => None, 
+            _ => Err(RuntimeError::InvalidOperation(format!("{:?} is not a unary operator", operator))),
         }
     }
-    
-    fn perform_comparison_operation(&self, left: &DataHolder, operator: &crate::tokenizer::ComparisonOperator, right: &DataHolder) -> Option<DataHolder> {
+
+    fn perform_comparison_operation(&self, left: &DataHolder, operator: &crate::tokenizer::ComparisonOperator, right: &DataHolder) -> Result<DataHolder, RuntimeError> {
+        let promoted = Self::promote_operands(left, right);
+        let (left, right) = match &promoted {
+            Some((left, right)) => (left, right),
+            None => (left, right),
+        };
+
         match operator {
-            crate::tokenizer::ComparisonOperator::Equal => {
-                match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::BOOLEAN(a == b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::BOOLEAN(a == b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::BOOLEAN((a - b).abs() < f32::EPSILON)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::BOOLEAN((a - b).abs() < f64::EPSILON)),
-                    (DataHolder::STRING(a), DataHolder::STRING(b)) => Some(DataHolder::BOOLEAN(a == b)),
-                    (DataHolder::BOOLEAN(a), DataHolder::BOOLEAN(b)) => Some(DataHolder::BOOLEAN(a == b)),
-                    _ => Some(DataHolder::BOOLEAN(false)),
-                }
-            },
-            crate::tokenizer::ComparisonOperator::NotEqual => {
-                if let Some(DataHolder::BOOLEAN(result)) = self.perform_comparison_operation(left, &crate::tokenizer::ComparisonOperator::Equal, right) {
-                    Some(DataHolder::BOOLEAN(!result))
-                } else {
-                    None
-                }
-            },
+            crate::tokenizer::ComparisonOperator::Equal => Ok(DataHolder::BOOLEAN(values_equal(left, right))),
+            crate::tokenizer::ComparisonOperator::NotEqual => Ok(DataHolder::BOOLEAN(!values_equal(left, right))),
             crate::tokenizer::ComparisonOperator::Greater => {
                 match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::BOOLEAN(a > b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::BOOLEAN(a > b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::BOOLEAN(a > b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::BOOLEAN(a > b)),
-                    _ => None,
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Ok(DataHolder::BOOLEAN(a > b)),
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Ok(DataHolder::BOOLEAN(a > b)),
+                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Ok(DataHolder::BOOLEAN(a > b)),
+                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Ok(DataHolder::BOOLEAN(a > b)),
+                    _ => Err(RuntimeError::unsupported_operands(">", &left.get_type(), &right.get_type())),
                 }
             },
             crate::tokenizer::ComparisonOperator::Less => {
                 match (left, right) {
-                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Some(DataHolder::BOOLEAN(a < b)),
-                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Some(DataHolder::BOOLEAN(a < b)),
-                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Some(DataHolder::BOOLEAN(a < b)),
-                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Some(DataHolder::BOOLEAN(a < b)),
-                    _ => None,
+                    (DataHolder::INTEGER32(a), DataHolder::INTEGER32(b)) => Ok(DataHolder::BOOLEAN(a < b)),
+                    (DataHolder::INTEGER64(a), DataHolder::INTEGER64(b)) => Ok(DataHolder::BOOLEAN(a < b)),
+                    (DataHolder::FLOAT32(a), DataHolder::FLOAT32(b)) => Ok(DataHolder::BOOLEAN(a < b)),
+                    (DataHolder::FLOAT64(a), DataHolder::FLOAT64(b)) => Ok(DataHolder::BOOLEAN(a < b)),
+                    _ => Err(RuntimeError::unsupported_operands("<", &left.get_type(), &right.get_type())),
                 }
             },
             crate::tokenizer::ComparisonOperator::GreaterEqual => {
-                if let Some(DataHolder::BOOLEAN(less_result)) = self.perform_comparison_operation(left, &crate::tokenizer::ComparisonOperator::Less, right) {
-                    Some(DataHolder::BOOLEAN(!less_result))
-                } else {
-                    None
+                match self.perform_comparison_operation(left, &crate::tokenizer::ComparisonOperator::Less, right)? {
+                    DataHolder::BOOLEAN(less_result) => Ok(DataHolder::BOOLEAN(!less_result)),
+                    _ => unreachable!("ordering always yields a boolean"),
                 }
             },
             crate::tokenizer::ComparisonOperator::LessEqual => {
-                if let Some(DataHolder::BOOLEAN(greater_result)) = self.perform_comparison_operation(left, &crate::tokenizer::ComparisonOperator::Greater, right) {
-                    Some(DataHolder::BOOLEAN(!greater_result))
-                } else {
-                    None
+                match self.perform_comparison_operation(left, &crate::tokenizer::ComparisonOperator::Greater, right)? {
+                    DataHolder::BOOLEAN(greater_result) => Ok(DataHolder::BOOLEAN(!greater_result)),
+                    _ => unreachable!("ordering always yields a boolean"),
                 }
             },
         }
     }
-    
-    pub fn call_function(&mut self, func_name: &str, args: Vec<DataHolder>) -> Option<DataHolder> {
-    if let Some(function) = self.functions.get(func_name).cloned() {
-        let mut function_env = Environment::new();
-        
-        
-        for (key, value) in self.environment.get_all_variables() {
-            function_env.set_variable(key.clone(), value.clone());
-        }
-        
-        
-        let non_self_params: Vec<_> = function.params.iter()
-            .filter(|param| param.name != "self")
-            .collect();
-        
-        if args.len() != non_self_params.len() {
-            eprintln!("Function '{}' expects {} arguments, got {}", func_name, non_self_params.len(), args.len());
-            return None;
-        }
-        
-        
-        for (param, arg) in non_self_params.iter().zip(args.iter()) {
-            function_env.set_variable(param.name.clone(), arg.clone());
-        }
-        
-        let old_env = std::mem::replace(&mut self.environment, function_env);
-        let old_returning = self.returning;
-        let old_return_value = self.return_value.clone();
-        
-        self.returning = false;
-        self.return_value = None;
-        
-        for statement in function.body {
-            let result = self.execute_statement(statement);
-            if matches!(result, ExecutionResult::Return(_)) {
-                break;
-            }
-        }
-        
-        let return_val = self.return_value.clone().unwrap_or(DataHolder::INTEGER32(0));
-        
-        self.environment = old_env;
-        self.returning = old_returning;
-        self.return_value = old_return_value;
-        
-        Some(return_val)
-    } else {
-        self.execute_builtin_function(func_name, args)
+
+    /// A function value that closes over the current scope.
+    fn make_function(&self, function: UserFunction) -> DataHolder {
+        DataHolder::FUNCTION(Rc::new(Callable::User { function, closure: Rc::clone(&self.environment) }))
     }
-}
-    
-    fn execute_builtin_function(&self, func_name: &str, args: Vec<DataHolder>) -> Option<DataHolder> {
-        
-        if let Ok(functions) = get_built_in_functions().lock() {
-            functions.call(func_name, args)
-        } else {
-            eprintln!("Error: Could not access built-in functions");
-            None
+
+    /// Calls a function value with already evaluated positional arguments.
+    pub fn call_value(&mut self, callee: &DataHolder, args: Vec<DataHolder>) -> Result<DataHolder, RuntimeError> {
+        self.call_with_arguments(callee, CallArguments { positional: args, keywords: Vec::new() })
+    }
+
+    pub fn call_with_arguments(&mut self, callee: &DataHolder, args: CallArguments) -> Result<DataHolder, RuntimeError> {
+        match callee {
+            DataHolder::FUNCTION(callable) => match callable.as_ref() {
+                // Inside the body, `self` is whatever the closure captured, not the caller's
+                // instance, so no method context is passed.
+                Callable::User { function, closure } => self.run_function(function, Rc::clone(closure), None, args),
+                Callable::BuiltIn(name) => self.execute_builtin_function(name, args),
+            },
+            other => Err(RuntimeError::TypeMismatch(format!("value of type {} is not callable", other.get_type()))),
         }
     }
-    
-    pub fn get_environment(&self) -> &Environment {
-        &self.environment
+
+    fn execute_builtin_function(&mut self, func_name: &str, args: CallArguments) -> Result<DataHolder, RuntimeError> {
+        if !args.keywords.is_empty() {
+            return Err(RuntimeError::argument_mismatch(func_name, "does not take keyword arguments".to_string()));
+        }
+        let args = args.positional;
+        // Copy the function out so the lock is released before it runs.
+        let function = match get_built_in_functions().lock() {
+            Ok(functions) => functions.get(func_name),
+            Err(_) => return Err(RuntimeError::InvalidOperation("could not access built-in functions".to_string())),
+        };
+        match function {
+            Some(function) => function(self, args),
+            None => Err(RuntimeError::UndefinedFunction(func_name.to_string())),
+        }
     }
-    
-    pub fn get_environment_mut(&mut self) -> &mut Environment {
-        &mut self.environment
+
+    /// Writes program output, such as the text of `print`, to this runtime's output.
+    pub fn write_output(&mut self, text: &str) -> Result<(), RuntimeError> {
+        self.output.write_all(text.as_bytes())
+            .map_err(|error| RuntimeError::InvalidOperation(format!("could not write output: {}", error)))
     }
-}
\ No newline at end of file
+}
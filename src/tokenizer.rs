@@ -1,4 +1,5 @@
-use std::{collections::HashMap, str};
+use std::{fmt, iter::Peekable, str::CharIndices};
+use crate::values::DataHolder;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Tokens {
@@ -15,8 +16,6 @@ pub enum Tokens {
     RPAREN,
     EQUALS,
     COMMA, 
-    DOUBLEQUOTES,
-    SINGLEQUOTES,
     LSQRBRAC,
     RSQRBRAC,
     IF,
@@ -36,12 +35,30 @@ pub enum Tokens {
     FOR,
     IN,
     DOT,
+    /// `...`, marking a rest parameter.
+    ELLIPSIS,
     FN,
     RETURN,
     WHILE,
     CLASS,
     PUBLIC,
     SELF,
+    ARROW,
+    BREAK,
+    CONTINUE,
+    STAR_STAR,
+    TILDE_SLASH,
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
+    LESS_LESS,
+    GREATER_GREATER,
+    PLUS_EQUALS,
+    MINUS_EQUALS,
+    STAR_EQUALS,
+    SLASH_EQUALS,
+    MODULO_EQUALS,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -53,61 +70,11 @@ pub enum Types{
     BOOLEAN,
     STRING,
     LIST,
-    NONE,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum DataHolder {
-    INTEGER32(i32),
-    INTEGER64(i64),
-    FLOAT32(f32),
-    FLOAT64(f64),
-    BOOLEAN(bool),
-    STRING(String),
-    LIST(Vec<DataHolder>),
-    FUNCTION(String),
-    CONDITIONAL_EXPRESSION(Box<ConditionalExpression>),
-    CLASSINSTANCE(ClassInstance),
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct ClassInstance {
-    pub class_name: String,
-    pub fields: HashMap<String, DataHolder>,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum ConditionalExpression {
-    
-    Comparison {
-        left: ExpressionNode,
-        operator: ComparisonOperator,
-        right: ExpressionNode,
-    },
-    
-    Logical {
-        left: Box<ConditionalExpression>,
-        operator: LogicalOperator,
-        right: Box<ConditionalExpression>,
-    },
-    
-    Value(ExpressionNode),
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum ExpressionNode {
-    Literal(DataHolder),
-    Variable(String),
-    BinaryOperation {
-        left: Box<ExpressionNode>,
-        operator: ArithmeticOperator,
-        right: Box<ExpressionNode>,
-    },
-    FunctionCall {
-        name: String,
-        args: Vec<ExpressionNode>,
-    },
-    ConditionalExpression(Box<ConditionalExpression>),
+    MAP,
+    /// A function value: a named function, a lambda or a builtin.
+    FUNCTION,
+    /// An instance of the named user-defined class.
+    CLASS(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -134,36 +101,228 @@ pub enum ArithmeticOperator {
     Divide,
     Modulo,
     Not,  
+    Power,
+    /// Division rounded down, as opposed to `Divide`, which truncates integers toward zero.
+    FloorDivide,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    BitNot,
 }
 
-impl DataHolder {
-    pub fn get_type(&self) -> Types {
+impl ArithmeticOperator {
+    pub fn symbol(&self) -> &'static str {
         match self {
-            DataHolder::INTEGER32(_) => Types::INTEGER32,
-            DataHolder::INTEGER64(_) => Types::INTEGER64,
-            DataHolder::FLOAT32(_) => Types::FLOAT32,
-            DataHolder::FLOAT64(_) => Types::FLOAT64,
-            DataHolder::BOOLEAN(_) => Types::BOOLEAN,
-            DataHolder::STRING(_) => Types::STRING,
-            DataHolder::LIST(_) => Types::LIST,
-            DataHolder::FUNCTION(_) => Types::STRING,
-            _ => Types::NONE,
+            ArithmeticOperator::Add => "+",
+            ArithmeticOperator::Subtract => "-",
+            ArithmeticOperator::Multiply => "*",
+            ArithmeticOperator::Divide => "/",
+            ArithmeticOperator::Modulo => "%",
+            ArithmeticOperator::Not => "!",
+            ArithmeticOperator::Power => "**",
+            ArithmeticOperator::FloorDivide => "~/",
+            ArithmeticOperator::BitAnd => "&",
+            ArithmeticOperator::BitOr => "|",
+            ArithmeticOperator::BitXor => "^",
+            ArithmeticOperator::ShiftLeft => "<<",
+            ArithmeticOperator::ShiftRight => ">>",
+            ArithmeticOperator::BitNot => "~",
+        }
+    }
+}
+
+impl Types {
+    /// Whether a value of type `actual` may be stored where `self` is declared. Besides an
+    /// exact match, an i32 widens to i64 or f64, and a float converts to either float width,
+    /// so `let h: f32 = 5.9` works even though float literals are f64.
+    pub fn accepts(&self, actual: &Types) -> bool {
+        self == actual
+            || matches!(
+                (self, actual),
+                (Types::INTEGER64, Types::INTEGER32)
+                    | (Types::FLOAT64, Types::INTEGER32)
+                    | (Types::FLOAT64, Types::FLOAT32)
+                    | (Types::FLOAT32, Types::FLOAT64)
+            )
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Types::INTEGER32 | Types::INTEGER64 | Types::FLOAT32 | Types::FLOAT64)
+    }
+
+    /// The type two numeric operands are converted to before arithmetic or comparison. Operands
+    /// of the same type stay as they are. Two different integers meet at i64, an integer and a
+    /// float take the float's type, and f32 with f64 gives f64. `None` unless both are numeric.
+    pub fn promote(left: &Types, right: &Types) -> Option<Types> {
+        if !left.is_numeric() || !right.is_numeric() {
+            return None;
+        }
+        if left == right {
+            return Some(left.clone());
+        }
+        match (left, right) {
+            (Types::FLOAT64, _) | (_, Types::FLOAT64) => Some(Types::FLOAT64),
+            (Types::FLOAT32, _) | (_, Types::FLOAT32) => Some(Types::FLOAT32),
+            _ => Some(Types::INTEGER64),
+        }
+    }
+}
+
+impl fmt::Display for Types {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Types::INTEGER32 => "i32",
+            Types::INTEGER64 => "i64",
+            Types::FLOAT32 => "f32",
+            Types::FLOAT64 => "f64",
+            Types::BOOLEAN => "bool",
+            Types::STRING => "string",
+            Types::LIST => "list",
+            Types::MAP => "map",
+            Types::FUNCTION => "fn",
+            Types::CLASS(name) => name,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Tokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Tokens::IDENTIFIER(name) => return write!(f, "identifier '{}'", name),
+            Tokens::TYPE(data_type) => return write!(f, "type '{}'", data_type),
+            Tokens::VALUE(DataHolder::STRING(_)) => return write!(f, "string literal"),
+            Tokens::VALUE(DataHolder::BOOLEAN(value)) => return write!(f, "'{}'", value),
+            Tokens::VALUE(_) => return write!(f, "number literal"),
+            Tokens::LET => "let",
+            Tokens::COLON => ":",
+            Tokens::PLUS => "+",
+            Tokens::MINUS => "-",
+            Tokens::STAR => "*",
+            Tokens::SLASH => "/",
+            Tokens::LPAREN => "(",
+            Tokens::RPAREN => ")",
+            Tokens::EQUALS => "=",
+            Tokens::COMMA => ",",
+            Tokens::LSQRBRAC => "[",
+            Tokens::RSQRBRAC => "]",
+            Tokens::IF => "if",
+            Tokens::ELSE => "else",
+            Tokens::LBRACE => "{",
+            Tokens::RBRACE => "}",
+            Tokens::EQUALS_EQUALS => "==",
+            Tokens::GREATER => ">",
+            Tokens::LESS => "<",
+            Tokens::OR => "or",
+            Tokens::AND => "and",
+            Tokens::NOT => "!",
+            Tokens::NOT_EQUALS => "!=",
+            Tokens::LESS_EQUALS => "<=",
+            Tokens::GREATER_EQUALS => ">=",
+            Tokens::MODULO => "%",
+            Tokens::FOR => "for",
+            Tokens::IN => "in",
+            Tokens::DOT => ".",
+            Tokens::ELLIPSIS => "...",
+            Tokens::FN => "fn",
+            Tokens::RETURN => "return",
+            Tokens::WHILE => "while",
+            Tokens::CLASS => "class",
+            Tokens::PUBLIC => "public",
+            Tokens::SELF => "self",
+            Tokens::ARROW => "->",
+            Tokens::BREAK => "break",
+            Tokens::CONTINUE => "continue",
+            Tokens::STAR_STAR => "**",
+            Tokens::TILDE_SLASH => "~/",
+            Tokens::AMPERSAND => "&",
+            Tokens::PIPE => "|",
+            Tokens::CARET => "^",
+            Tokens::TILDE => "~",
+            Tokens::LESS_LESS => "<<",
+            Tokens::GREATER_GREATER => ">>",
+            Tokens::PLUS_EQUALS => "+=",
+            Tokens::MINUS_EQUALS => "-=",
+            Tokens::STAR_EQUALS => "*=",
+            Tokens::SLASH_EQUALS => "/=",
+            Tokens::MODULO_EQUALS => "%=",
+        };
+        write!(f, "'{}'", symbol)
+    }
+}
+
+/// Location of a token or syntax node in the source: byte offsets plus the
+/// 1-based line and column (in characters) of `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Span covering everything from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end.max(self.end),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: Tokens,
+    pub span: Span,
+}
+
+struct LineIndex<'a> {
+    content: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(content: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        for (offset, ch) in content.char_indices() {
+            if ch == '\n' {
+                line_starts.push(offset + 1);
+            }
+        }
+        LineIndex { content, line_starts }
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        let line = self.line_starts.partition_point(|&line_start| line_start <= start);
+        let line_start = self.line_starts[line - 1];
+        Span {
+            start,
+            end,
+            line,
+            column: self.content[line_start..start].chars().count() + 1,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Callable{
-    Build_in : BuildInFunction
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub message: String,
+    pub span: Span,
 }
 
-#[derive(Debug, Clone)]
-struct BuildInFunction{
-    name: String,
-    param_count: i32,
-    body: fn(Vec<DataHolder>) -> DataHolder,
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
 }
 
+impl std::error::Error for LexError {}
+
 pub struct Tokenizer;
 
 impl Tokenizer {
@@ -171,271 +330,288 @@ impl Tokenizer {
         Tokenizer
     }
 
-    
-    pub fn process_content(&self, content: &str) -> Vec<Tokens> {
-        let mut tokens = Vec::new();
-        let mut token = String::new();
-        let mut chars = content.chars().peekable();
-
-        while let Some(char) = chars.next() {
-            match char {
-                ' ' | '\t' | '\n' | '\r' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    
-                }
-                '"' => {
-                    
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    
-                    let mut string_content = String::new();
-                    let mut escaped = false;
-                    
-                    
-                    while let Some(inner_char) = chars.next() {
-                        if escaped {
-                            
-                            match inner_char {
-                                'n' => string_content.push('\n'),
-                                't' => string_content.push('\t'),
-                                'r' => string_content.push('\r'),
-                                '\\' => string_content.push('\\'),
-                                '"' => string_content.push('"'),
-                                '\'' => string_content.push('\''),
-                                _ => {
-                                    string_content.push('\\');
-                                    string_content.push(inner_char);
-                                }
-                            }
-                            escaped = false;
-                        } else if inner_char == '\\' {
-                            escaped = true;
-                        } else if inner_char == '"' {
-                            break; 
-                        } else {
-                            string_content.push(inner_char);
-                        }
-                    }
-                    
-                    tokens.push(Tokens::VALUE(DataHolder::STRING(string_content)));
-                }
-                '\'' => {
-                    
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    
-                    let mut string_content = String::new();
-                    let mut escaped = false;
-                    
-                    
-                    while let Some(inner_char) = chars.next() {
-                        if escaped {
-                            
-                            match inner_char {
-                                'n' => string_content.push('\n'),
-                                't' => string_content.push('\t'),
-                                'r' => string_content.push('\r'),
-                                '\\' => string_content.push('\\'),
-                                '"' => string_content.push('"'),
-                                '\'' => string_content.push('\''),
-                                _ => {
-                                    string_content.push('\\');
-                                    string_content.push(inner_char);
-                                }
-                            }
-                            escaped = false;
-                        } else if inner_char == '\\' {
-                            escaped = true;
-                        } else if inner_char == '\'' {
-                            break; 
-                        } else {
-                            string_content.push(inner_char);
-                        }
-                    }
-                    
-                    tokens.push(Tokens::VALUE(DataHolder::STRING(string_content)));
-                }
-                '+' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::PLUS);
-                }
-                '-' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::MINUS);
-                }
-                '*' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::STAR);
-                }
-                '/' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::SLASH);
-                }
-                '(' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::LPAREN);
-                }
-                ')' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::RPAREN);
-                }
-                ':' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::COLON);
-                }
-                '[' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::LSQRBRAC);
-                }
-                ',' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::COMMA);
-                }
-                ']' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::RSQRBRAC);
-                }
-                '{' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::LBRACE);
-                }
-                '}' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::RBRACE);
-                }
-                '=' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-
-                    
-                    if chars.peek() == Some(&'=') {
-                        chars.next(); 
-                        tokens.push(Tokens::EQUALS_EQUALS);
-                    } else {
-                        
-                        match tokens.last() {
-                            Some(&Tokens::NOT) => {
-                                tokens.pop();
-                                tokens.push(Tokens::NOT_EQUALS);
-                            }
-                            Some(&Tokens::LESS) => {
-                                tokens.pop();
-                                tokens.push(Tokens::LESS_EQUALS);
-                            }
-                            Some(&Tokens::GREATER) => {
-                                tokens.pop();
-                                tokens.push(Tokens::GREATER_EQUALS);
-                            }
-                            _ => {
-                                tokens.push(Tokens::EQUALS);
-                            }
+    /// Splits `content` into tokens, skipping whitespace and comments. Stops at the first
+    /// character that can't start a token, or at a string, comment or number that is malformed.
+    pub fn process_content(&self, content: &str) -> Result<Vec<Token>, LexError> {
+        Lexer::new(content).run()
+    }
+}
+
+struct Lexer<'a> {
+    content: &'a str,
+    lines: LineIndex<'a>,
+    chars: Peekable<CharIndices<'a>>,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(content: &'a str) -> Self {
+        Lexer {
+            content,
+            lines: LineIndex::new(content),
+            chars: content.char_indices().peekable(),
+            tokens: Vec::new(),
+        }
+    }
+
+    fn run(mut self) -> Result<Vec<Token>, LexError> {
+        while let Some((offset, ch)) = self.chars.next() {
+            match ch {
+                '/' if self.next_is('/') => self.skip_line_comment(),
+                '/' if self.next_is('*') => self.skip_block_comment(offset)?,
+                '"' | '\'' => self.lex_string(offset, ch)?,
+                '0'..='9' => self.lex_number(offset, ch)?,
+                _ if ch.is_whitespace() => {}
+                _ if ch == '_' || ch.is_alphabetic() => self.lex_word(offset),
+                _ => self.lex_symbol(offset, ch)?,
+            }
+        }
+        Ok(self.tokens)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, ch)| ch)
+    }
+
+    /// The character after the one `peek` returns.
+    fn peek_second(&self) -> Option<char> {
+        self.chars.clone().nth(1).map(|(_, ch)| ch)
+    }
+
+    fn next_is(&mut self, expected: char) -> bool {
+        self.peek() == Some(expected)
+    }
+
+    /// Byte offset just past the last character consumed.
+    fn position(&mut self) -> usize {
+        self.chars.peek().map_or(self.content.len(), |&(offset, _)| offset)
+    }
+
+    fn push(&mut self, kind: Tokens, start: usize) {
+        let end = self.position();
+        self.tokens.push(Token { kind, span: self.lines.span(start, end) });
+    }
+
+    fn error(&self, message: impl Into<String>, start: usize, end: usize) -> LexError {
+        LexError { message: message.into(), span: self.lines.span(start, end) }
+    }
+
+    fn skip_line_comment(&mut self) {
+        while self.peek().is_some_and(|ch| ch != '\n') {
+            self.chars.next();
+        }
+    }
+
+    /// Skips a `/* ... */` comment. Block comments don't nest: the first `*/` ends it.
+    fn skip_block_comment(&mut self, start: usize) -> Result<(), LexError> {
+        self.chars.next();
+        while let Some((_, ch)) = self.chars.next() {
+            if ch == '*' && self.next_is('/') {
+                self.chars.next();
+                return Ok(());
+            }
+        }
+        Err(self.error("unterminated block comment", start, start + 2))
+    }
+
+    /// Reads a string closed by the same quote that opened it. A string must end on the line
+    /// it starts on; write `\n` for a line break inside one.
+    fn lex_string(&mut self, start: usize, quote: char) -> Result<(), LexError> {
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, ch)) if ch == quote => break,
+                Some((escape_start, '\\')) => {
+                    let escaped = match self.peek() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some('\\') => '\\',
+                        Some('"') => '"',
+                        Some('\'') => '\'',
+                        Some(other) if other != '\n' => {
+                            let end = escape_start + 1 + other.len_utf8();
+                            return Err(self.error(format!("unknown escape sequence '\\{}'", other), escape_start, end));
                         }
-                    }
-                }
-                '>' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::GREATER);
+                        _ => return Err(self.error("unterminated string literal", start, start + 1)),
+                    };
+                    self.chars.next();
+                    value.push(escaped);
                 }
-                '<' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::LESS);
+                Some((_, '\n')) | None => {
+                    return Err(self.error("unterminated string literal", start, start + 1));
                 }
-                '!' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    
-                    
-                    if chars.peek() == Some(&'=') {
-                        chars.next(); 
-                        tokens.push(Tokens::NOT_EQUALS);
-                    } else {
-                        tokens.push(Tokens::NOT);
-                    }
-                }
-                '%' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::MODULO);
-                }
-                '.' => {
-                    if !token.is_empty() {
-                        tokens.push(self.classify_token(&token));
-                        token.clear();
-                    }
-                    tokens.push(Tokens::DOT);
-                }
-                _ => token.push(char),
+                Some((_, ch)) => value.push(ch),
+            }
+        }
+        self.push(Tokens::VALUE(DataHolder::STRING(value)), start);
+        Ok(())
+    }
+
+    /// Reads a decimal, `0x` hexadecimal, `0o` octal or `0b` binary integer, or a decimal
+    /// float with a fraction and/or an `e` exponent. `_` may separate digits anywhere in the
+    /// literal. Integers that fit in an `i32` are `i32`, larger ones `i64`; floats are `f64`.
+    fn lex_number(&mut self, start: usize, first: char) -> Result<(), LexError> {
+        let (radix, radix_name) = match (first, self.peek()) {
+            ('0', Some('x' | 'X')) => (16, "hexadecimal"),
+            ('0', Some('o' | 'O')) => (8, "octal"),
+            ('0', Some('b' | 'B')) => (2, "binary"),
+            _ => (10, "decimal"),
+        };
+
+        if radix != 10 {
+            self.chars.next();
+            let digits_start = self.position();
+            self.skip_word_chars();
+            let end = self.position();
+            let digits: String = self.content[digits_start..end].chars().filter(|&ch| ch != '_').collect();
+            if let Some(invalid) = digits.chars().find(|ch| !ch.is_digit(radix)) {
+                return Err(self.error(format!("invalid digit '{}' in {} literal", invalid, radix_name), start, end));
+            }
+            if digits.is_empty() {
+                return Err(self.error(format!("{} literal has no digits", radix_name), start, end));
             }
+            let value = i64::from_str_radix(&digits, radix).map_err(|_| {
+                self.error(format!("integer literal '{}' is too large for i64", &self.content[start..end]), start, end)
+            })?;
+            self.push(Tokens::VALUE(Self::integer_value(value)), start);
+            return Ok(());
         }
 
-        if !token.is_empty() {
-            tokens.push(self.classify_token(&token));
+        let mut is_float = false;
+        self.skip_digits();
+        if self.next_is('.') && self.peek_second().is_some_and(|ch| ch.is_ascii_digit()) {
+            is_float = true;
+            self.chars.next();
+            self.skip_digits();
         }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let mut lookahead = self.chars.clone();
+            lookahead.next();
+            if matches!(lookahead.peek(), Some((_, '+' | '-'))) {
+                lookahead.next();
+            }
+            if lookahead.peek().is_some_and(|(_, ch)| ch.is_ascii_digit()) {
+                is_float = true;
+                self.chars = lookahead;
+                self.skip_digits();
+            }
+        }
+
+        // Letters straight after a number, as in `12ab` or `1.5y`, are a typo rather than
+        // a separate identifier.
+        if self.peek().is_some_and(|ch| ch == '_' || ch.is_alphanumeric()) {
+            self.skip_word_chars();
+            let end = self.position();
+            return Err(self.error(format!("invalid number literal '{}'", &self.content[start..end]), start, end));
+        }
+
+        let end = self.position();
+        let text: String = self.content[start..end].chars().filter(|&ch| ch != '_').collect();
+        let value = if is_float {
+            DataHolder::FLOAT64(text.parse::<f64>().expect("float literal is validated by the lexer"))
+        } else {
+            let value = text.parse::<i64>().map_err(|_| {
+                self.error(format!("integer literal '{}' is too large for i64", &self.content[start..end]), start, end)
+            })?;
+            Self::integer_value(value)
+        };
+        self.push(Tokens::VALUE(value), start);
+        Ok(())
+    }
+
+    fn integer_value(value: i64) -> DataHolder {
+        match i32::try_from(value) {
+            Ok(value) => DataHolder::INTEGER32(value),
+            Err(_) => DataHolder::INTEGER64(value),
+        }
+    }
 
-        tokens
+    fn skip_digits(&mut self) {
+        while self.peek().is_some_and(|ch| ch == '_' || ch.is_ascii_digit()) {
+            self.chars.next();
+        }
+    }
+
+    fn skip_word_chars(&mut self) {
+        while self.peek().is_some_and(|ch| ch == '_' || ch.is_alphanumeric()) {
+            self.chars.next();
+        }
     }
 
-    
-    pub fn process_line(&self, line: &str) -> Vec<Tokens> {
-        self.process_content(line)
+    fn lex_word(&mut self, start: usize) {
+        self.skip_word_chars();
+        let end = self.position();
+        let kind = Self::classify_word(&self.content[start..end]);
+        self.push(kind, start);
+    }
+
+    fn lex_symbol(&mut self, start: usize, ch: char) -> Result<(), LexError> {
+        if ch == '.' && self.next_is('.') && self.peek_second() == Some('.') {
+            self.chars.next();
+            self.chars.next();
+            self.push(Tokens::ELLIPSIS, start);
+            return Ok(());
+        }
+
+        let pair = match (ch, self.peek()) {
+            ('-', Some('>')) => Some(Tokens::ARROW),
+            ('=', Some('=')) => Some(Tokens::EQUALS_EQUALS),
+            ('!', Some('=')) => Some(Tokens::NOT_EQUALS),
+            ('<', Some('=')) => Some(Tokens::LESS_EQUALS),
+            ('>', Some('=')) => Some(Tokens::GREATER_EQUALS),
+            ('+', Some('=')) => Some(Tokens::PLUS_EQUALS),
+            ('-', Some('=')) => Some(Tokens::MINUS_EQUALS),
+            ('*', Some('=')) => Some(Tokens::STAR_EQUALS),
+            ('/', Some('=')) => Some(Tokens::SLASH_EQUALS),
+            ('%', Some('=')) => Some(Tokens::MODULO_EQUALS),
+            ('*', Some('*')) => Some(Tokens::STAR_STAR),
+            ('~', Some('/')) => Some(Tokens::TILDE_SLASH),
+            ('<', Some('<')) => Some(Tokens::LESS_LESS),
+            ('>', Some('>')) => Some(Tokens::GREATER_GREATER),
+            ('&', Some('&')) => Some(Tokens::AND),
+            ('|', Some('|')) => Some(Tokens::OR),
+            _ => None,
+        };
+        if let Some(kind) = pair {
+            self.chars.next();
+            self.push(kind, start);
+            return Ok(());
+        }
+
+        let kind = match ch {
+            '+' => Tokens::PLUS,
+            '-' => Tokens::MINUS,
+            '*' => Tokens::STAR,
+            '/' => Tokens::SLASH,
+            '(' => Tokens::LPAREN,
+            ')' => Tokens::RPAREN,
+            ':' => Tokens::COLON,
+            '[' => Tokens::LSQRBRAC,
+            ']' => Tokens::RSQRBRAC,
+            ',' => Tokens::COMMA,
+            '{' => Tokens::LBRACE,
+            '}' => Tokens::RBRACE,
+            '>' => Tokens::GREATER,
+            '<' => Tokens::LESS,
+            '%' => Tokens::MODULO,
+            '&' => Tokens::AMPERSAND,
+            '|' => Tokens::PIPE,
+            '^' => Tokens::CARET,
+            '~' => Tokens::TILDE,
+            '.' => Tokens::DOT,
+            '=' => Tokens::EQUALS,
+            '!' => Tokens::NOT,
+            _ => {
+                return Err(self.error(format!("unexpected character '{}'", ch), start, start + ch.len_utf8()));
+            }
+        };
+        self.push(kind, start);
+        Ok(())
     }
 
-    fn classify_token(&self, word: &str) -> Tokens {
+    fn classify_word(word: &str) -> Tokens {
         match word {
             "let" => Tokens::LET,
             "for" => Tokens::FOR,
@@ -446,6 +622,8 @@ impl Tokenizer {
             "f64" => Tokens::TYPE(Types::FLOAT64),
             "bool" => Tokens::TYPE(Types::BOOLEAN),
             "string" => Tokens::TYPE(Types::STRING),
+            "list" => Tokens::TYPE(Types::LIST),
+            "map" => Tokens::TYPE(Types::MAP),
             "true" => Tokens::VALUE(DataHolder::BOOLEAN(true)),
             "false" => Tokens::VALUE(DataHolder::BOOLEAN(false)),
             "if" => Tokens::IF,
@@ -456,37 +634,12 @@ impl Tokenizer {
             "fn" => Tokens::FN,
             "return" => Tokens::RETURN,
             "while" => Tokens::WHILE,
+            "break" => Tokens::BREAK,
+            "continue" => Tokens::CONTINUE,
             "class" => Tokens::CLASS,
             "public" => Tokens::PUBLIC,
             "self" => Tokens::SELF,
-            _ => {
-                if let Some(value) = self.try_parse_number(word) {
-                    return Tokens::VALUE(value);
-                }
-                Tokens::IDENTIFIER(word.to_string())
-            }
-        }
-    }
-
-    fn try_parse_number(&self, word: &str) -> Option<DataHolder> {
-        if let Ok(value) = word.parse::<i32>() {
-            return Some(DataHolder::INTEGER32(value));
-        }
-
-        if let Ok(value) = word.parse::<i64>() {
-            return Some(DataHolder::INTEGER64(value));
+            _ => Tokens::IDENTIFIER(word.to_string()),
         }
-
-        if word.contains('.') {
-            if let Ok(value) = word.parse::<f32>() {
-                return Some(DataHolder::FLOAT32(value));
-            }
-            
-            if let Ok(value) = word.parse::<f64>() {
-                return Some(DataHolder::FLOAT64(value));
-            }
-        }
-
-        None
     }
-}
\ No newline at end of file
+}
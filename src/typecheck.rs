@@ -0,0 +1,822 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::tokenizer::{Types, Span, ArithmeticOperator, ComparisonOperator};
+use crate::AstTree::{class_attributes, Statement, AstExpressions, FunctionParameter, KeywordArgument};
+use crate::Functions::get_built_in_functions;
+use crate::Methods::get_native_methods;
+use crate::runtime::{ArgumentSource, Runtime, RuntimeError};
+
+/// A problem found before the program runs. The message is worded exactly as the runtime
+/// would word the same failure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+#[derive(Debug, Clone)]
+struct Signature {
+    params: Vec<FunctionParameter>,
+    return_type: Option<Types>,
+}
+
+impl Signature {
+    fn new(params: &[FunctionParameter], return_type: &Option<Types>) -> Self {
+        Signature {
+            params: params.iter().filter(|param| param.name != "self").cloned().collect(),
+            return_type: return_type.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ClassInfo {
+    fields: HashMap<String, Types>,
+    /// Field names in declaration order.
+    field_order: Vec<String>,
+    methods: HashMap<String, Signature>,
+}
+
+/// One level of nesting: a block, a loop or a function body.
+#[derive(Clone, Default)]
+struct Scope {
+    variables: HashMap<String, Option<Types>>,
+    /// Functions declared in this scope. Top-level ones are in `TypeChecker::functions`.
+    functions: HashMap<String, Signature>,
+    /// Bodies of the functions and lambdas defined in this scope, checked when it ends.
+    pending: Vec<PendingBody>,
+}
+
+/// A function or method body waiting to be checked once every name it can see is known.
+#[derive(Clone)]
+struct PendingBody {
+    name: String,
+    signature: Signature,
+    body: Vec<Statement>,
+    self_class: Option<String>,
+}
+
+/// Walks the AST before it runs, resolving names and inferring expression types.
+///
+/// Types are `Option<Types>`: `None` means the type can't be known statically (an untyped
+/// function's result, a builtin's result, anything derived from an earlier error), and no
+/// check is made against it. That keeps the pass free of false alarms at the cost of missing
+/// some errors the runtime will still catch.
+///
+/// Scoping follows the runtime: blocks nest, and function bodies see the scope they are
+/// defined in but not their caller's locals. A body is checked once the scope it is defined
+/// in has ended, so that names declared below it, which exist by the time it can be called,
+/// are visible in it. The checker keeps its state between calls, so the REPL can check each
+/// input against everything entered before.
+pub struct TypeChecker {
+    scopes: Vec<Scope>,
+    functions: HashMap<String, Signature>,
+    classes: HashMap<String, ClassInfo>,
+    /// Name and declared return type of the function body being checked.
+    current_function: Option<(String, Option<Types>)>,
+    /// Top-level function, method and lambda bodies, checked once the top level is done.
+    pending: Vec<PendingBody>,
+    errors: Vec<TypeError>,
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        TypeChecker {
+            scopes: vec![Scope::default()],
+            functions: HashMap::new(),
+            classes: HashMap::new(),
+            current_function: None,
+            pending: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Checks `statements` against everything checked before. If they fail, nothing they
+    /// declared is kept: they won't run, so later input must not see their names.
+    pub fn check(&mut self, statements: &[Statement]) -> Result<(), Vec<TypeError>> {
+        let scopes = self.scopes.clone();
+        let functions = self.functions.clone();
+        let classes = self.classes.clone();
+        self.collect_declarations(statements);
+
+        for statement in statements {
+            self.check_statement(statement);
+        }
+
+        while !self.pending.is_empty() {
+            let body = self.pending.remove(0);
+            self.check_body(body);
+        }
+
+        self.errors.sort_by_key(|error| error.span.start);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            self.scopes = scopes;
+            self.functions = functions;
+            self.classes = classes;
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Registers top-level functions and classes up front so calls may appear before the
+    /// declaration in the file, e.g. in mutually recursive functions.
+    fn collect_declarations(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            match statement {
+                Statement::FunctionDeclaration { name, params, return_type, .. } => {
+                    self.functions.insert(name.clone(), Signature::new(params, return_type));
+                },
+                Statement::ClassMeta { name, fields, .. } => {
+                    self.declare_class(name, fields);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn declare_class(&mut self, name: &str, members: &HashMap<String, Statement>) {
+        let mut info = ClassInfo {
+            field_order: class_attributes(members).into_iter().map(|(field, _)| field).collect(),
+            ..ClassInfo::default()
+        };
+        for (member_name, member) in members {
+            match member {
+                Statement::ClassAttribute { data_type, .. } => {
+                    info.fields.insert(member_name.clone(), data_type.clone());
+                },
+                Statement::FunctionDeclaration { params, return_type, .. } => {
+                    info.methods.insert(member_name.clone(), Signature::new(params, return_type));
+                },
+                _ => {},
+            }
+        }
+        self.classes.insert(name.to_string(), info);
+    }
+
+    fn check_body(&mut self, pending_body: PendingBody) {
+        let function_scopes = vec![self.scopes[0].clone(), Scope::default()];
+        let saved_scopes = std::mem::replace(&mut self.scopes, function_scopes);
+        let saved_function = self.current_function.replace((pending_body.name.clone(), pending_body.signature.return_type.clone()));
+
+        if let Some(class_name) = pending_body.self_class {
+            self.declare("self", Some(Types::CLASS(class_name)));
+        }
+        self.declare_parameters(&pending_body.name, &pending_body.signature);
+        for statement in &pending_body.body {
+            self.check_statement(statement);
+        }
+        self.check_pending_bodies();
+
+        self.current_function = saved_function;
+        let function_scopes = std::mem::replace(&mut self.scopes, saved_scopes);
+
+        // Assignments inside the body may have created or retyped globals.
+        if let Some(globals) = function_scopes.into_iter().next() {
+            self.scopes[0] = globals;
+        }
+    }
+
+    fn error(&mut self, span: Span, error: RuntimeError) {
+        self.errors.push(TypeError { message: error.to_string(), span });
+    }
+
+    fn declare(&mut self, name: &str, data_type: Option<Types>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.functions.remove(name);
+            scope.variables.insert(name.to_string(), data_type);
+        }
+    }
+
+    /// `Some(type)` if `name` is visible; the inner option is the known type, if any.
+    fn lookup(&self, name: &str) -> Option<Option<Types>> {
+        self.scopes.iter().rev().find_map(|scope| scope.variables.get(name).cloned())
+    }
+
+    /// The signature of a function declared inside another function or a block, unless a
+    /// variable in a nearer scope hides it.
+    fn local_function(&self, name: &str) -> Option<Signature> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(signature) = scope.functions.get(name) {
+                return Some(signature.clone());
+            }
+            if scope.variables.contains_key(name) {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    /// Ends the innermost scope, after checking the bodies defined in it.
+    fn pop_scope(&mut self) {
+        self.check_pending_bodies();
+        self.scopes.pop();
+    }
+
+    /// Checks the bodies waiting on the innermost scope, which is complete by now.
+    fn check_pending_bodies(&mut self) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        for pending_body in std::mem::take(&mut scope.pending) {
+            let saved_function = self.current_function.replace((pending_body.name.clone(), pending_body.signature.return_type.clone()));
+            self.push_scope();
+            self.declare_parameters(&pending_body.name, &pending_body.signature);
+            for statement in &pending_body.body {
+                self.check_statement(statement);
+            }
+            self.pop_scope();
+            self.current_function = saved_function;
+        }
+    }
+
+    fn check_block(&mut self, statements: &[Statement]) {
+        self.push_scope();
+        for statement in statements {
+            self.check_statement(statement);
+        }
+        self.pop_scope();
+    }
+
+    fn expect_type(&mut self, span: Span, context: &str, expected: &Types, actual: &Option<Types>) {
+        if let Some(actual) = actual
+            && !expected.accepts(actual) {
+            self.error(span, RuntimeError::wrong_type(context, expected, actual));
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::VariableDeclaration { name, data_type, value, .. } => {
+                let value_type = self.check_expression(value);
+                match data_type {
+                    Some(data_type) => {
+                        self.expect_type(value.span(), &format!("variable '{}'", name), data_type, &value_type);
+                        self.declare(name, Some(data_type.clone()));
+                    },
+                    None => self.declare(name, value_type),
+                }
+            },
+
+            Statement::Assignment { name, value, .. } => {
+                let value_type = self.check_expression(value);
+                match self.lookup(name) {
+                    Some(Some(declared)) => {
+                        self.expect_type(value.span(), &format!("variable '{}'", name), &declared, &value_type);
+                    },
+                    Some(None) => {},
+                    None => self.declare(name, value_type),
+                }
+            },
+
+            Statement::MemberAssignment { object, member, value, span } => {
+                let value_type = self.check_expression(value);
+                match self.check_expression(object) {
+                    Some(Types::CLASS(class_name)) => {
+                        let field_type = self.classes.get(&class_name).and_then(|class| class.fields.get(member).cloned());
+                        match field_type {
+                            Some(field_type) => {
+                                let context = format!("field '{}' of '{}'", member, class_name);
+                                self.expect_type(value.span(), &context, &field_type, &value_type);
+                            },
+                            None => self.error(*span, RuntimeError::UnknownField { type_name: class_name, field: member.clone() }),
+                        }
+                    },
+                    Some(other) => self.error(*span, RuntimeError::TypeMismatch(format!(
+                        "cannot assign to member '{}' on value of type {}", member, other
+                    ))),
+                    None => {},
+                }
+            },
+
+            Statement::IndexAssignment { object, index, value, span } => {
+                self.check_expression(value);
+                let object_type = self.check_expression(object);
+                self.check_subscript(&object_type, index);
+                match object_type {
+                    Some(Types::LIST | Types::MAP) | None => {},
+                    Some(other) => self.error(*span, RuntimeError::TypeMismatch(format!(
+                        "cannot assign to an index of value of type {}", other
+                    ))),
+                }
+            },
+
+            // Checked as `target = target op value`, which has the same types.
+            Statement::CompoundAssignment { target, operator, value, span } => {
+                let value = AstExpressions::BinaryOperation {
+                    left: Box::new(target.clone()),
+                    operator: operator.clone(),
+                    right: Box::new(value.clone()),
+                    span: target.span().to(value.span()),
+                };
+                let assignment = match target {
+                    AstExpressions::Variable { name, .. } => Statement::Assignment { name: name.clone(), value, span: *span },
+                    AstExpressions::MemberAccess { object, member, .. } => Statement::MemberAssignment {
+                        object: (**object).clone(),
+                        member: member.clone(),
+                        value,
+                        span: *span,
+                    },
+                    AstExpressions::Index { object, index, .. } => Statement::IndexAssignment {
+                        object: (**object).clone(),
+                        index: (**index).clone(),
+                        value,
+                        span: *span,
+                    },
+                    _ => return,
+                };
+                self.check_statement(&assignment);
+            },
+
+            Statement::Conditional { condition, then_branch, else_branch, .. } => {
+                self.check_expression(condition);
+                self.check_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check_block(else_branch);
+                }
+            },
+
+            Statement::ForLoop { variable, start, end, step, body, span } => {
+                let bounds = [self.check_expression(start), self.check_expression(end), self.check_expression(step)];
+                let loop_type = match &bounds {
+                    [Some(a), Some(b), Some(c)] if a == b && b == c && matches!(a, Types::INTEGER32 | Types::INTEGER64) => Some(a.clone()),
+                    [Some(a), Some(b), Some(c)] => {
+                        self.error(*span, RuntimeError::TypeMismatch(format!(
+                            "for loop range requires matching integer start, end and step, got {}, {} and {}", a, b, c
+                        )));
+                        None
+                    },
+                    _ => None,
+                };
+                self.push_scope();
+                self.declare(variable, loop_type);
+                self.check_block(body);
+                self.pop_scope();
+            },
+
+            Statement::ForEach { variable, iterable, body, span } => {
+                let item_type = match self.check_expression(iterable) {
+                    Some(Types::STRING) => Some(Types::STRING),
+                    Some(Types::LIST | Types::MAP) | None => None,
+                    Some(other) => {
+                        self.error(*span, RuntimeError::TypeMismatch(format!("value of type {} is not iterable", other)));
+                        None
+                    },
+                };
+                self.push_scope();
+                self.declare(variable, item_type);
+                self.check_block(body);
+                self.pop_scope();
+            },
+
+            Statement::WhileLoop { condition, body, .. } => {
+                self.check_expression(condition);
+                self.check_block(body);
+            },
+
+            Statement::Block { statements, .. } => self.check_block(statements),
+
+            Statement::ExpressionStatement { expression, .. } => {
+                self.check_expression(expression);
+            },
+
+            Statement::Return { value, span } => {
+                let value_type = match value {
+                    Some(value) => self.check_expression(value),
+                    None => Some(Types::INTEGER32),
+                };
+                if let Some((function_name, Some(expected))) = self.current_function.clone() {
+                    let span = value.as_ref().map_or(*span, |value| value.span());
+                    self.expect_type(span, &format!("return value of '{}'", function_name), &expected, &value_type);
+                }
+            },
+
+            Statement::FunctionDeclaration { name, params, return_type, body, .. } => {
+                let signature = Signature::new(params, return_type);
+                if self.scopes.len() == 1 {
+                    self.functions.insert(name.clone(), signature.clone());
+                } else {
+                    // At runtime a nested function is a variable of the scope it is declared in.
+                    self.declare(name, Some(Types::FUNCTION));
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.functions.insert(name.clone(), signature.clone());
+                    }
+                }
+                self.check_function_body(name.clone(), signature, body);
+            },
+
+            Statement::ClassMeta { name, fields, .. } => {
+                self.declare_class(name, fields);
+                for (method_name, member) in fields {
+                    if let Statement::FunctionDeclaration { params, return_type, body, .. } = member {
+                        self.pending.push(PendingBody {
+                            name: format!("{}.{}", name, method_name),
+                            signature: Signature::new(params, return_type),
+                            body: body.clone(),
+                            self_class: Some(name.clone()),
+                        });
+                    }
+                }
+            },
+
+            Statement::Break { .. } | Statement::Continue { .. } => {},
+
+            Statement::ClassAttribute { name, span, .. } => {
+                self.error(*span, RuntimeError::InvalidOperation(format!("Class attribute '{}' declared outside of a class", name)));
+            },
+        }
+    }
+
+    /// Queues a function or lambda body. At the top level it waits, like every top-level body,
+    /// until all globals are known; anywhere else, until the scope it is defined in ends.
+    fn check_function_body(&mut self, name: String, signature: Signature, body: &[Statement]) {
+        let pending_body = PendingBody { name, signature, body: body.to_vec(), self_class: None };
+        if self.scopes.len() == 1 {
+            self.pending.push(pending_body);
+        } else if let Some(scope) = self.scopes.last_mut() {
+            scope.pending.push(pending_body);
+        }
+    }
+
+    /// Declares the parameters in the current scope. A default is checked just before its
+    /// parameter is declared, as the runtime evaluates it then.
+    fn declare_parameters(&mut self, name: &str, signature: &Signature) {
+        for param in &signature.params {
+            if let Some(default) = &param.default {
+                let default_type = self.check_expression(default);
+                let context = format!("parameter '{}' of '{}'", param.name, name);
+                self.expect_type(default.span(), &context, &param.data_type, &default_type);
+            }
+            self.declare(&param.name, Some(param.data_type.clone()));
+        }
+    }
+
+    /// Checks the argument expressions of a call whose parameters aren't known.
+    fn check_argument_expressions(&mut self, arguments: &[AstExpressions], keyword_arguments: &[KeywordArgument]) {
+        for argument in arguments.iter().chain(keyword_arguments.iter().map(|(_, argument)| argument)) {
+            self.check_expression(argument);
+        }
+    }
+
+    /// A builtin or native method takes no keyword arguments.
+    fn reject_keywords(&mut self, name: &str, keyword_arguments: &[KeywordArgument], span: Span) {
+        if !keyword_arguments.is_empty() {
+            self.error(span, RuntimeError::argument_mismatch(name, "does not take keyword arguments".to_string()));
+        }
+    }
+
+    /// Checks the arguments of a call to a function value, whose parameters aren't known.
+    fn check_value_call(&mut self, callee_type: Option<Types>, arguments: &[AstExpressions], keyword_arguments: &[KeywordArgument], span: Span) {
+        self.check_argument_expressions(arguments, keyword_arguments);
+        if let Some(other) = callee_type
+            && other != Types::FUNCTION {
+            self.error(span, RuntimeError::TypeMismatch(format!("value of type {} is not callable", other)));
+        }
+    }
+
+    fn is_builtin(name: &str) -> bool {
+        get_built_in_functions().lock()
+            .map(|functions| functions.has_function(name))
+            .unwrap_or(true)
+    }
+
+    fn check_arguments(&mut self, name: &str, signature: &Signature, arguments: &[AstExpressions], keyword_arguments: &[KeywordArgument], span: Span) {
+        let argument_types: Vec<_> = arguments.iter().map(|argument| self.check_expression(argument)).collect();
+        let keyword_types: Vec<_> = keyword_arguments.iter().map(|(_, argument)| self.check_expression(argument)).collect();
+
+        let keywords: Vec<&str> = keyword_arguments.iter().map(|(keyword, _)| keyword.as_str()).collect();
+        let matched = match Runtime::match_arguments(name, &signature.params, arguments.len(), &keywords) {
+            Ok(matched) => matched,
+            Err(error) => {
+                self.error(span, error);
+                return;
+            },
+        };
+
+        for (param, source) in matched {
+            let (argument, argument_type) = match source {
+                ArgumentSource::Positional(index) => (&arguments[index], &argument_types[index]),
+                ArgumentSource::Keyword(index) => (&keyword_arguments[index].1, &keyword_types[index]),
+                ArgumentSource::Default(_) | ArgumentSource::Rest(_) => continue,
+            };
+            let context = format!("parameter '{}' of '{}'", param.name, name);
+            self.expect_type(argument.span(), &context, &param.data_type, argument_type);
+        }
+    }
+
+    fn check_expression(&mut self, expression: &AstExpressions) -> Option<Types> {
+        match expression {
+            AstExpressions::Value { value, .. } => Some(value.get_type()),
+
+            AstExpressions::Variable { name, span } => match self.lookup(name) {
+                Some(data_type) => data_type,
+                None if self.functions.contains_key(name) || Self::is_builtin(name) => Some(Types::FUNCTION),
+                None => {
+                    self.error(*span, RuntimeError::UndefinedVariable(name.clone()));
+                    None
+                },
+            },
+
+            AstExpressions::BinaryOperation { left, operator, right, span } => {
+                // Check both sides before giving up on an unknown type, so errors on the right
+                // are still reported.
+                let left_type = self.check_expression(left);
+                let right_type = self.check_expression(right);
+                let (left_type, right_type) = (left_type?, right_type?);
+                let result = Self::arithmetic_result(operator, &left_type, &right_type);
+                if result.is_none() {
+                    self.error(*span, RuntimeError::unsupported_operands(operator.symbol(), &left_type, &right_type));
+                }
+                result
+            },
+
+            AstExpressions::UnaryOperation { operator, operand, span } => {
+                let operand_type = self.check_expression(operand)?;
+                let numeric = operand_type.is_numeric();
+                match operator {
+                    ArithmeticOperator::Subtract if numeric => Some(operand_type),
+                    ArithmeticOperator::Subtract => {
+                        self.error(*span, RuntimeError::TypeMismatch(format!("bad operand type for unary -: {}", operand_type)));
+                        None
+                    },
+                    ArithmeticOperator::Not if matches!(operand_type, Types::BOOLEAN | Types::INTEGER32 | Types::INTEGER64) => Some(Types::BOOLEAN),
+                    ArithmeticOperator::Not => {
+                        self.error(*span, RuntimeError::TypeMismatch(format!("bad operand type for !: {}", operand_type)));
+                        None
+                    },
+                    ArithmeticOperator::BitNot if matches!(operand_type, Types::INTEGER32 | Types::INTEGER64) => Some(operand_type),
+                    ArithmeticOperator::BitNot => {
+                        self.error(*span, RuntimeError::TypeMismatch(format!("bad operand type for ~: {}", operand_type)));
+                        None
+                    },
+                    _ => Some(operand_type),
+                }
+            },
+
+            AstExpressions::ComparisonOperation { left, operator, right, span } => {
+                let left_type = self.check_expression(left);
+                let right_type = self.check_expression(right);
+                if let (Some(left_type), Some(right_type)) = (left_type, right_type) {
+                    let symbol = match operator {
+                        ComparisonOperator::Greater | ComparisonOperator::LessEqual => Some(">"),
+                        ComparisonOperator::Less | ComparisonOperator::GreaterEqual => Some("<"),
+                        ComparisonOperator::Equal | ComparisonOperator::NotEqual => None,
+                    };
+                    if let Some(symbol) = symbol
+                        && Types::promote(&left_type, &right_type).is_none() {
+                        self.error(*span, RuntimeError::unsupported_operands(symbol, &left_type, &right_type));
+                    }
+                }
+                Some(Types::BOOLEAN)
+            },
+
+            AstExpressions::LogicalOperation { left, operator, right, span } => {
+                let left_type = self.check_expression(left);
+                let right_type = self.check_expression(right);
+                let keyword = match operator {
+                    crate::tokenizer::LogicalOperator::And => "and",
+                    crate::tokenizer::LogicalOperator::Or => "or",
+                };
+                // Worded as the runtime does: a bad left operand stops it before the right runs.
+                match (left_type, right_type) {
+                    (Some(left_type), _) if left_type != Types::BOOLEAN => {
+                        self.error(*span, RuntimeError::logical_operand(keyword, &left_type, None));
+                    },
+                    (Some(left_type), Some(right_type)) if right_type != Types::BOOLEAN => {
+                        self.error(*span, RuntimeError::logical_operand(keyword, &left_type, Some(&right_type)));
+                    },
+                    _ => {},
+                }
+                Some(Types::BOOLEAN)
+            },
+
+            AstExpressions::ListLiteral { elements, .. } => {
+                for element in elements {
+                    self.check_expression(element);
+                }
+                Some(Types::LIST)
+            },
+
+            AstExpressions::MapLiteral { entries, .. } => {
+                for (key, value) in entries {
+                    self.check_map_key(key);
+                    self.check_expression(value);
+                }
+                Some(Types::MAP)
+            },
+
+            AstExpressions::FunctionCall { name, arguments, keyword_arguments, span } => {
+                if let Some(class) = self.classes.get(name) {
+                    match class.methods.get("__init__").cloned() {
+                        Some(init) => self.check_arguments(&format!("{}.__init__", name), &init, arguments, keyword_arguments, *span),
+                        None => {
+                            // Without `__init__`, class and function fields never get a value.
+                            let unset: Vec<String> = class.field_order.iter()
+                                .filter(|field| Runtime::get_default_value(&class.fields[*field]).is_none())
+                                .cloned()
+                                .collect();
+                            self.check_argument_expressions(arguments, keyword_arguments);
+                            if !arguments.is_empty() || !keyword_arguments.is_empty() {
+                                self.error(*span, RuntimeError::argument_mismatch(name, "takes no arguments, as it has no '__init__'".to_string()));
+                            }
+                            if !unset.is_empty() {
+                                self.error(*span, RuntimeError::MissingFields { type_name: name.clone(), fields: unset });
+                            }
+                        },
+                    }
+                    return Some(Types::CLASS(name.clone()));
+                }
+
+                if let Some(signature) = self.local_function(name) {
+                    self.check_arguments(name, &signature, arguments, keyword_arguments, *span);
+                    return signature.return_type;
+                }
+
+                // A variable holding a function shadows a declared function of the same name.
+                if let Some(variable_type) = self.lookup(name) {
+                    self.check_value_call(variable_type, arguments, keyword_arguments, *span);
+                    return None;
+                }
+
+                if let Some(signature) = self.functions.get(name).cloned() {
+                    self.check_arguments(name, &signature, arguments, keyword_arguments, *span);
+                    return signature.return_type;
+                }
+
+                self.check_argument_expressions(arguments, keyword_arguments);
+                if Self::is_builtin(name) {
+                    self.reject_keywords(name, keyword_arguments, *span);
+                } else {
+                    self.error(*span, RuntimeError::UndefinedFunction(name.clone()));
+                }
+                None
+            },
+
+            AstExpressions::Call { callee, arguments, keyword_arguments, span } => {
+                let callee_type = self.check_expression(callee);
+                self.check_value_call(callee_type, arguments, keyword_arguments, *span);
+                None
+            },
+
+            AstExpressions::InstanceLiteral { class_name, fields, span } => {
+                let value_types: Vec<_> = fields.iter().map(|(_, value)| self.check_expression(value)).collect();
+                let Some(class) = self.classes.get(class_name).cloned() else {
+                    self.error(*span, RuntimeError::UndefinedClass(class_name.clone()));
+                    return None;
+                };
+
+                let given: Vec<&str> = fields.iter().map(|(field, _)| field.as_str()).collect();
+                if let Err(error) = Runtime::check_literal_fields(class_name, &class.field_order, &given) {
+                    self.error(*span, error);
+                }
+                for ((field, value), value_type) in fields.iter().zip(&value_types) {
+                    if let Some(field_type) = class.fields.get(field) {
+                        let context = format!("field '{}' of '{}'", field, class_name);
+                        self.expect_type(value.span(), &context, field_type, value_type);
+                    }
+                }
+                Some(Types::CLASS(class_name.clone()))
+            },
+
+            AstExpressions::Lambda { params, return_type, body, .. } => {
+                self.check_function_body("lambda".to_string(), Signature::new(params, return_type), body);
+                Some(Types::FUNCTION)
+            },
+
+            AstExpressions::MemberAccess { object, member, span } => {
+                match self.check_expression(object)? {
+                    Types::CLASS(class_name) => {
+                        let field_type = self.classes.get(&class_name).and_then(|class| class.fields.get(member).cloned());
+                        if field_type.is_none() {
+                            self.error(*span, RuntimeError::UnknownField { type_name: class_name, field: member.clone() });
+                        }
+                        field_type
+                    },
+                    other => {
+                        self.error(*span, RuntimeError::UnknownField { type_name: other.to_string(), field: member.clone() });
+                        None
+                    },
+                }
+            },
+
+            AstExpressions::MethodCall { object, method, arguments, keyword_arguments, span } => {
+                let object_type = self.check_expression(object);
+                let Some(Types::CLASS(class_name)) = object_type else {
+                    self.check_argument_expressions(arguments, keyword_arguments);
+                    let other = object_type?;
+                    match get_native_methods().get(&other, method) {
+                        Some(native) => {
+                            self.reject_keywords(&format!("{}.{}", other, method), keyword_arguments, *span);
+                            return native.return_type.clone();
+                        },
+                        None => {
+                            self.error(*span, RuntimeError::UnknownMethod { type_name: other.to_string(), method: method.clone() });
+                            return None;
+                        },
+                    }
+                };
+
+                let class = self.classes.get(&class_name);
+                let signature = class.and_then(|class| class.methods.get(method).cloned());
+                if signature.is_none() && class.and_then(|class| class.fields.get(method)) == Some(&Types::FUNCTION) {
+                    self.check_value_call(Some(Types::FUNCTION), arguments, keyword_arguments, *span);
+                    return None;
+                }
+                match signature {
+                    Some(signature) => {
+                        self.check_arguments(&format!("{}.{}", class_name, method), &signature, arguments, keyword_arguments, *span);
+                        signature.return_type
+                    },
+                    None => {
+                        self.error(*span, RuntimeError::UnknownMethod { type_name: class_name, method: method.clone() });
+                        None
+                    },
+                }
+            },
+
+            AstExpressions::Grouping { expression, .. } => self.check_expression(expression),
+
+            AstExpressions::Index { object, index, span } => {
+                let object_type = self.check_expression(object);
+                self.check_subscript(&object_type, index);
+                match object_type? {
+                    Types::LIST | Types::MAP => None,
+                    Types::STRING => Some(Types::STRING),
+                    other => {
+                        self.error(*span, RuntimeError::TypeMismatch(format!("value of type {} cannot be indexed", other)));
+                        None
+                    },
+                }
+            },
+
+            AstExpressions::Slice { object, start, end, span } => {
+                let object_type = self.check_expression(object);
+                for bound in [start, end].into_iter().flatten() {
+                    self.check_index(bound);
+                }
+                match object_type? {
+                    sequence @ (Types::LIST | Types::STRING) => Some(sequence),
+                    other => {
+                        self.error(*span, RuntimeError::TypeMismatch(format!("value of type {} cannot be sliced", other)));
+                        None
+                    },
+                }
+            },
+        }
+    }
+
+    /// Checks what goes between the brackets of `object[...]`: a key when `object` is a map, a
+    /// position when it is anything else.
+    fn check_subscript(&mut self, object_type: &Option<Types>, index: &AstExpressions) {
+        match object_type {
+            Some(Types::MAP) => self.check_map_key(index),
+            Some(_) => self.check_index(index),
+            None => {
+                self.check_expression(index);
+            },
+        }
+    }
+
+    fn check_map_key(&mut self, key: &AstExpressions) {
+        match self.check_expression(key) {
+            Some(Types::STRING | Types::INTEGER32 | Types::INTEGER64 | Types::BOOLEAN) | None => {},
+            Some(other) => self.error(key.span(), RuntimeError::TypeMismatch(format!("map keys must be strings, integers or bools, got {}", other))),
+        }
+    }
+
+    fn check_index(&mut self, index: &AstExpressions) {
+        match self.check_expression(index) {
+            Some(Types::INTEGER32 | Types::INTEGER64) | None => {},
+            Some(other) => self.error(index.span(), RuntimeError::TypeMismatch(format!("indices must be integers, got {}", other))),
+        }
+    }
+
+    /// The static counterpart of `Runtime::perform_arithmetic_operation`: `None` when the
+    /// runtime would reject the operands.
+    fn arithmetic_result(operator: &ArithmeticOperator, left: &Types, right: &Types) -> Option<Types> {
+        match operator {
+            ArithmeticOperator::Not | ArithmeticOperator::BitNot => None,
+            ArithmeticOperator::Add if *left == Types::STRING && *right == Types::STRING => Some(Types::STRING),
+            ArithmeticOperator::BitAnd
+            | ArithmeticOperator::BitOr
+            | ArithmeticOperator::BitXor
+            | ArithmeticOperator::ShiftLeft
+            | ArithmeticOperator::ShiftRight => {
+                Types::promote(left, right).filter(|common| matches!(common, Types::INTEGER32 | Types::INTEGER64))
+            },
+            _ => Types::promote(left, right),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Runtime values. The lexer produces them for literals, so they live apart from both the
+//! lexer and the runtime.
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+use crate::tokenizer::Types;
+use crate::AstTree::{FunctionParameter, Statement};
+use crate::Environment::Environment;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataHolder {
+    INTEGER32(i32),
+    INTEGER64(i64),
+    FLOAT32(f32),
+    FLOAT64(f64),
+    BOOLEAN(bool),
+    STRING(String),
+    LIST(Vec<DataHolder>),
+    /// Key-value pairs in insertion order. Keys are strings, integers or booleans and are unique.
+    MAP(Vec<(DataHolder, DataHolder)>),
+    FUNCTION(Rc<Callable>),
+    /// Instances are shared: copying the value copies the reference, so a field written
+    /// through one alias (including `self` inside a method) is seen through all of them.
+    CLASSINSTANCE(Rc<RefCell<ClassInstance>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassInstance {
+    pub class_name: String,
+    pub fields: HashMap<String, DataHolder>,
+}
+
+impl DataHolder {
+    pub fn get_type(&self) -> Types {
+        match self {
+            DataHolder::INTEGER32(_) => Types::INTEGER32,
+            DataHolder::INTEGER64(_) => Types::INTEGER64,
+            DataHolder::FLOAT32(_) => Types::FLOAT32,
+            DataHolder::FLOAT64(_) => Types::FLOAT64,
+            DataHolder::BOOLEAN(_) => Types::BOOLEAN,
+            DataHolder::STRING(_) => Types::STRING,
+            DataHolder::LIST(_) => Types::LIST,
+            DataHolder::MAP(_) => Types::MAP,
+            DataHolder::FUNCTION(_) => Types::FUNCTION,
+            DataHolder::CLASSINSTANCE(instance) => Types::CLASS(instance.borrow().class_name.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserFunction {
+    /// The declared name, or `lambda` for an anonymous function.
+    pub name: String,
+    pub params: Vec<FunctionParameter>,
+    pub return_type: Option<Types>,
+    pub body: Vec<Statement>,
+}
+
+/// What a `DataHolder::FUNCTION` value calls.
+pub enum Callable {
+    /// A declared function or a lambda, together with the scope it was defined in. Its body
+    /// runs in a new scope on top of `closure`, so it sees the variables around its
+    /// definition, and sees later changes to them, for as long as the function value lives.
+    User {
+        function: UserFunction,
+        closure: Rc<RefCell<Environment>>,
+    },
+    /// A builtin from `Functions.rs`, by name.
+    BuiltIn(String),
+}
+
+impl Callable {
+    pub fn name(&self) -> &str {
+        match self {
+            Callable::User { function, .. } => &function.name,
+            Callable::BuiltIn(name) => name,
+        }
+    }
+}
+
+/// Written by hand: the captured scope can contain the function itself.
+impl fmt::Debug for Callable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+/// Two function values are equal when they are the same function: the same declaration or
+/// lambda evaluation, or the same builtin.
+impl PartialEq for Callable {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Callable::BuiltIn(left), Callable::BuiltIn(right)) => left == right,
+            _ => std::ptr::eq(self, other),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::path::Path;
+
+use OxyPy::golden;
+
+#[test]
+fn golden_scripts() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts");
+    let results = golden::run_directory(&dir).expect("could not read tests/scripts");
+    assert!(!results.is_empty(), "no .oxy scripts found in {}", dir.display());
+
+    let failures: Vec<String> = results.iter()
+        .filter(|result| !result.passed())
+        .map(|result| {
+            let mismatches: Vec<String> = result.mismatches.iter().map(|mismatch| mismatch.to_string()).collect();
+            format!("{}\n{}", result.script.display(), mismatches.join("\n"))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{} of {} golden scripts failed:\n\n{}", failures.len(), results.len(), failures.join("\n\n"));
+}
@@ -0,0 +1,40 @@
+use OxyPy::interpreter::{run_source, with_large_stack};
+use OxyPy::runtime::MAX_RECURSION_LIMIT;
+
+/// Recursion at the highest limit a script may set, through a method whose body nests deeply
+/// enough that each call needs a lot of native stack, must end in an error, not a crash.
+#[test]
+fn deep_bodies_stop_at_the_recursion_limit() {
+    let source = format!(r#"
+class N {{
+    public {{
+        v: i32
+    }}
+    public {{
+        fn go(self, n: i32) -> i32 {{
+            for i in range(1) {{
+                while (true) {{
+                    if (n >= 0) {{
+                        if (n <= {limit}) {{
+                            return [N {{ v: 1 }}.go(n + 1)][0]
+                        }}
+                    }}
+                    return n
+                }}
+            }}
+            return n
+        }}
+    }}
+}}
+set_recursion_limit({limit})
+println(N {{ v: 1 }}.go(0))
+"#, limit = MAX_RECURSION_LIMIT);
+
+    let (succeeded, errors) = with_large_stack(|| {
+        let mut errors = Vec::new();
+        let succeeded = run_source(&source, "deep.oxy", false, Box::new(std::io::sink()), &mut errors);
+        (succeeded, String::from_utf8_lossy(&errors).into_owned())
+    });
+    assert!(!succeeded);
+    assert!(errors.contains("error: recursion limit exceeded"), "unexpected errors:\n{}", errors);
+}